@@ -0,0 +1,868 @@
+use crate::services::file_name_template::FileNameTemplate;
+use crate::services::folder_scanner::scan_folder;
+use crate::services::image_service::{
+    BackupTarget, CompressionParams, ExistingFilePolicy, ImageService, JpegOptions,
+    LargerOutputPolicy, OutputAction, OutputFormat,
+};
+use crate::views::results_view::{format_pct, format_size};
+use std::collections::BTreeMap;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+const USAGE: &str = "Usage: compressr [OPTIONS] (--output <PATH> | --in-place) <INPUT>...
+
+Compresses the given image files and folders without opening the user interface.
+
+Options:
+  -o, --output <PATH>     Output directory, or output file when compressing a single image
+      --in-place          Replace the input files, backing up the originals into a
+                          .compressr-backup folder next to them; a converted file
+                          that would replace another file is renamed instead
+      --backup-dir <DIR>  With --in-place, back up the originals into DIR instead,
+                          recreating the input folders below it
+  -f, --format <FORMAT>   Output format: jpeg, png, gif, webp, bmp, tiff, avif, jxl,
+                          original [default: jpeg]
+      --map <SRC=DST>     With --format original, write SRC inputs as DST (e.g. png=webp);
+                          may be repeated
+  -q, --quality <1-100>   JPEG/WebP/AVIF quality, and PNG quality with --lossy-png
+                          [default: 100]. JPEG XL is always lossless
+      --target-size <KIB> Search the highest quality that fits within this size
+      --downscale         Downscale images that exceed --target-size at the lowest quality
+      --min-similarity <PCT>
+                          Search the lowest quality that keeps this SSIM similarity;
+                          not supported for AVIF outputs
+      --if-larger <POLICY>
+                          When the output grows: write, keep (copy the original), skip [default: write]
+      --if-exists <POLICY>
+                          When the output file exists: overwrite, skip, rename, ask
+                          [default: overwrite] (ask skips existing files when not run
+                          from a terminal)
+      --progressive       Write progressive JPEGs
+      --subsampling <MODE>
+                          JPEG chroma subsampling: 444, 422, 420 [default: 420]
+      --optimize-huffman  Compute optimized Huffman tables for JPEGs
+      --trellis           Use trellis quantization for JPEGs (slower, smaller)
+      --lossless          Recompress JPEG inputs without decoding them when their size
+                          is kept; quality and target options do not apply to them
+      --avif-speed <1-10> AVIF encoder speed, lower is slower but smaller [default: 6]
+      --png-level <0-6>   PNG optimization level [default: 2]
+      --zopfli            Recompress PNG data with Zopfli (much slower)
+      --lossy-png         Reduce PNG colors to a palette when --quality is below 100
+      --max-fps <FPS>     Drop frames of animated GIF/WebP outputs above this frame rate
+      --optimize-gif-palette
+                          Share one optimized palette across animated GIF frames
+  -s, --scale <1-100>     Scale factor in percent [default: 100]
+      --width <PIXELS>    Output width in pixels
+      --height <PIXELS>   Output height in pixels
+      --preserve-exif     Copy EXIF metadata from the source files
+  -r, --recursive         Scan input folders recursively
+      --name <TEMPLATE>   Output file name template [default: {stem}_compressed.{ext}]
+                          Tokens: {stem}, {ext}, {format}, {width}, {height}, {quality},
+                          {date}, {index}, {hash8}
+      --preserve-structure
+                          Recreate the input folder structure below the output directory
+  -h, --help              Print this help text
+  -V, --version           Print the version";
+
+/// Every option [`parse_args`] accepts; one of them selects the headless mode.
+const OPTIONS: &[&str] = &[
+    "-h",
+    "--help",
+    "-V",
+    "--version",
+    "-o",
+    "--output",
+    "--in-place",
+    "--backup-dir",
+    "-f",
+    "--format",
+    "--map",
+    "-q",
+    "--quality",
+    "--target-size",
+    "--downscale",
+    "--min-similarity",
+    "--if-larger",
+    "--if-exists",
+    "--progressive",
+    "--subsampling",
+    "--optimize-huffman",
+    "--trellis",
+    "--lossless",
+    "--avif-speed",
+    "--png-level",
+    "--zopfli",
+    "--lossy-png",
+    "--max-fps",
+    "--optimize-gif-palette",
+    "-s",
+    "--scale",
+    "--width",
+    "--height",
+    "--preserve-exif",
+    "-r",
+    "--recursive",
+    "--preserve-structure",
+    "--name",
+];
+
+/// Returns whether the command line asks for a headless run rather than the user interface.
+///
+/// Plain paths, as passed by "Open with" or by dropping files onto the executable, open the
+/// user interface; only a recognized option starts the command line mode.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments, without the program name.
+///
+/// # Returns
+///
+/// `true` if any argument before a `--` separator is one of the command line options.
+pub fn is_cli_invocation(args: &[String]) -> bool {
+    args.iter().take_while(|arg| *arg != "--").any(|arg| {
+        let name = arg.split_once('=').map_or(arg.as_str(), |(name, _)| name);
+        OPTIONS.contains(&name)
+    })
+}
+
+/// Options parsed from the command line for a headless compression run.
+#[derive(Debug)]
+struct CliOptions {
+    inputs: Vec<String>,
+    output: Option<String>,
+    in_place: bool,
+    backup_dir: Option<String>,
+    format: OutputFormat,
+    format_mapping: BTreeMap<OutputFormat, OutputFormat>,
+    quality: u8,
+    target_size: Option<u64>,
+    downscale_to_target_size: bool,
+    min_similarity: Option<f64>,
+    larger_output_policy: LargerOutputPolicy,
+    existing_file_policy: ExistingFilePolicy,
+    jpeg_options: JpegOptions,
+    avif_speed: u8,
+    png_optimization_level: u8,
+    png_zopfli: bool,
+    lossy_png: bool,
+    max_frame_rate: Option<u32>,
+    optimize_gif_palette: bool,
+    scale: u32,
+    width: Option<u32>,
+    height: Option<u32>,
+    preserve_exif: bool,
+    recursive: bool,
+    preserve_structure: bool,
+    file_name_template: FileNameTemplate,
+}
+
+/// The action requested on the command line.
+enum CliCommand {
+    Compress(CliOptions),
+    Help,
+    Version,
+}
+
+/// Parses a numeric option value and validates that it falls within `range`.
+///
+/// # Arguments
+///
+/// * `name` - The option name, used in error messages.
+/// * `value` - The raw option value.
+/// * `range` - The inclusive range of accepted values.
+///
+/// # Returns
+///
+/// The parsed value, or an error message if it is not a number or out of range.
+fn parse_number<T>(name: &str, value: &str, range: std::ops::RangeInclusive<T>) -> Result<T, String>
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display,
+{
+    let parsed: T = value
+        .parse()
+        .map_err(|_| format!("Invalid value '{value}' for {name}"))?;
+    if !range.contains(&parsed) {
+        return Err(format!(
+            "Value for {name} must be between {} and {}",
+            range.start(),
+            range.end()
+        ));
+    }
+    Ok(parsed)
+}
+
+/// Parses the command line arguments (excluding the program name).
+///
+/// Options accept their value either as the next argument (`--quality 80`) or inline
+/// (`--quality=80`). Every argument that is not an option is treated as an input path.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments, without the program name.
+///
+/// # Returns
+///
+/// The parsed command, or an error message describing the invalid argument.
+fn parse_args(args: Vec<String>) -> Result<CliCommand, String> {
+    let mut inputs = Vec::new();
+    let mut output = None;
+    let mut in_place = false;
+    let mut backup_dir = None;
+    let mut format = OutputFormat::Jpeg;
+    let mut format_mapping = OutputFormat::default_mapping();
+    let mut quality = 100;
+    let mut target_size = None;
+    let mut downscale_to_target_size = false;
+    let mut min_similarity = None;
+    let mut larger_output_policy = LargerOutputPolicy::WriteAnyway;
+    let mut existing_file_policy = ExistingFilePolicy::Overwrite;
+    let mut jpeg_options = JpegOptions::default();
+    let mut avif_speed = 6;
+    let mut png_optimization_level = 2;
+    let mut png_zopfli = false;
+    let mut lossy_png = false;
+    let mut max_frame_rate = None;
+    let mut optimize_gif_palette = false;
+    let mut scale = 100;
+    let mut width = None;
+    let mut height = None;
+    let mut preserve_exif = false;
+    let mut recursive = false;
+    let mut preserve_structure = false;
+    let mut file_name_template = FileNameTemplate::default();
+
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            inputs.push(arg);
+            continue;
+        }
+        if arg == "--" {
+            inputs.extend(iter.by_ref());
+            break;
+        }
+
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) => (name.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| iter.next())
+                .ok_or_else(|| format!("Missing value for {name}"))
+        };
+
+        match name.as_str() {
+            "-h" | "--help" => return Ok(CliCommand::Help),
+            "-V" | "--version" => return Ok(CliCommand::Version),
+            "-o" | "--output" => output = Some(value()?),
+            "--in-place" => in_place = true,
+            "--backup-dir" => backup_dir = Some(value()?),
+            "-f" | "--format" => format = value()?.parse()?,
+            "--map" => {
+                let value = value()?;
+                let (source, target) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("Invalid value '{value}' for {name}"))?;
+                let source: OutputFormat = source.parse()?;
+                let target: OutputFormat = target.parse()?;
+                if !OutputFormat::SOURCES.contains(&source) || target == OutputFormat::Original {
+                    return Err(format!("Invalid value '{value}' for {name}"));
+                }
+                format_mapping.insert(source, target);
+            }
+            "-q" | "--quality" => quality = parse_number(&name, &value()?, 1..=100)?,
+            "--target-size" => {
+                let kib: u64 = parse_number(&name, &value()?, 1..=u64::MAX / 1024)?;
+                target_size = Some(kib * 1024);
+            }
+            "--downscale" => downscale_to_target_size = true,
+            "--min-similarity" => {
+                let pct: f64 = parse_number(&name, &value()?, 0.0..=100.0)?;
+                if pct == 0.0 {
+                    return Err(format!("Value for {name} must be greater than 0"));
+                }
+                min_similarity = Some(pct / 100.0);
+            }
+            "--if-larger" => {
+                larger_output_policy = match value()?.as_str() {
+                    "write" => LargerOutputPolicy::WriteAnyway,
+                    "keep" => LargerOutputPolicy::KeepOriginal,
+                    "skip" => LargerOutputPolicy::Skip,
+                    other => return Err(format!("Invalid value '{other}' for {name}")),
+                }
+            }
+            "--if-exists" => {
+                existing_file_policy = match value()?.as_str() {
+                    "overwrite" => ExistingFilePolicy::Overwrite,
+                    "skip" => ExistingFilePolicy::Skip,
+                    "rename" => ExistingFilePolicy::Rename,
+                    "ask" => ExistingFilePolicy::Ask,
+                    other => return Err(format!("Invalid value '{other}' for {name}")),
+                }
+            }
+            "--progressive" => jpeg_options.progressive = true,
+            "--subsampling" => jpeg_options.chroma_subsampling = value()?.parse()?,
+            "--optimize-huffman" => jpeg_options.optimize_huffman = true,
+            "--trellis" => jpeg_options.trellis = true,
+            "--lossless" => jpeg_options.lossless = true,
+            "--avif-speed" => avif_speed = parse_number(&name, &value()?, 1..=10)?,
+            "--png-level" => png_optimization_level = parse_number(&name, &value()?, 0..=6)?,
+            "--zopfli" => png_zopfli = true,
+            "--lossy-png" => lossy_png = true,
+            "--max-fps" => max_frame_rate = Some(parse_number(&name, &value()?, 1..=1000)?),
+            "--optimize-gif-palette" => optimize_gif_palette = true,
+            "-s" | "--scale" => scale = parse_number(&name, &value()?, 1..=100)?,
+            "--width" => width = Some(parse_number(&name, &value()?, 1..=u32::MAX)?),
+            "--height" => height = Some(parse_number(&name, &value()?, 1..=u32::MAX)?),
+            "--preserve-exif" => preserve_exif = true,
+            "-r" | "--recursive" => recursive = true,
+            "--preserve-structure" => preserve_structure = true,
+            "--name" => file_name_template = FileNameTemplate::parse(&value()?)?,
+            _ => return Err(format!("Unknown option '{name}'")),
+        }
+    }
+
+    match (&output, in_place) {
+        (None, false) => return Err("Missing required option --output".to_string()),
+        (Some(_), true) => return Err("--output cannot be combined with --in-place".to_string()),
+        _ => {}
+    }
+    if backup_dir.is_some() && !in_place {
+        return Err("--backup-dir requires --in-place".to_string());
+    }
+    if inputs.is_empty() {
+        return Err("No input files or folders specified".to_string());
+    }
+    let writes_avif = format == OutputFormat::Avif
+        || (format == OutputFormat::Original
+            && format_mapping
+                .values()
+                .any(|&target| target == OutputFormat::Avif));
+    if min_similarity.is_some() && writes_avif {
+        return Err("--min-similarity is not supported for AVIF output".to_string());
+    }
+
+    Ok(CliCommand::Compress(CliOptions {
+        inputs,
+        output,
+        in_place,
+        backup_dir,
+        format,
+        format_mapping,
+        quality,
+        target_size,
+        downscale_to_target_size,
+        min_similarity,
+        larger_output_policy,
+        existing_file_policy,
+        jpeg_options,
+        avif_speed,
+        png_optimization_level,
+        png_zopfli,
+        lossy_png,
+        max_frame_rate,
+        optimize_gif_palette,
+        scale,
+        width,
+        height,
+        preserve_exif,
+        recursive,
+        preserve_structure,
+        file_name_template,
+    }))
+}
+
+/// Expands the input arguments into a flat list of image files, scanning any folders.
+///
+/// # Arguments
+///
+/// * `inputs` - The input files and folders given on the command line.
+/// * `recursive` - Whether folders should be scanned recursively.
+///
+/// # Returns
+///
+/// The image files to compress, or an error message if an input does not exist or a folder
+/// could not be scanned.
+fn collect_files(inputs: &[String], recursive: bool) -> Result<Vec<String>, String> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            files.extend(scan_folder(PathBuf::from(input), recursive)?.paths());
+        } else if path.is_file() {
+            files.push(input.clone());
+        } else {
+            return Err(format!("Input '{input}' does not exist"));
+        }
+    }
+    Ok(files)
+}
+
+/// Finds the deepest folder that contains every input, so the input folder structure can be
+/// recreated below the output directory.
+///
+/// Inputs are canonicalized first so relative and absolute paths can be compared.
+///
+/// # Arguments
+///
+/// * `inputs` - The input files and folders given on the command line.
+///
+/// # Returns
+///
+/// The canonical inputs along with their common folder, or an error message if an input
+/// cannot be resolved or the inputs share no common folder.
+fn common_root(inputs: &[String]) -> Result<(Vec<String>, PathBuf), String> {
+    let mut canonical = Vec::with_capacity(inputs.len());
+    let mut root: Option<PathBuf> = None;
+    for input in inputs {
+        let path = std::fs::canonicalize(input)
+            .map_err(|e| format!("Input '{input}' could not be resolved: {e}"))?;
+        let folder = if path.is_dir() {
+            path.clone()
+        } else {
+            path.parent().map(Path::to_path_buf).unwrap_or_default()
+        };
+        root = Some(match root {
+            None => folder,
+            Some(root) => root
+                .components()
+                .zip(folder.components())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect(),
+        });
+        canonical.push(path.to_string_lossy().into_owned());
+    }
+
+    match root {
+        Some(root) if root.has_root() => Ok((canonical, root)),
+        _ => Err("Inputs do not share a common folder".to_string()),
+    }
+}
+
+/// Asks on the terminal what to do with output files that already exist.
+///
+/// # Arguments
+///
+/// * `existing` - The number of output files that already exist.
+///
+/// # Returns
+///
+/// The chosen policy, [`ExistingFilePolicy::Skip`] when stdin is not a terminal, or `None`
+/// when the user cancelled the batch.
+fn prompt_existing_file_policy(existing: usize) -> Option<ExistingFilePolicy> {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        eprintln!("{existing} output file(s) already exist and will be skipped");
+        return Some(ExistingFilePolicy::Skip);
+    }
+
+    loop {
+        print!(
+            "{existing} output file(s) already exist. [o]verwrite, [s]kip, [r]ename, [c]ancel? "
+        );
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        if stdin.lock().read_line(&mut answer).unwrap_or(0) == 0 {
+            return None;
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "o" | "overwrite" => return Some(ExistingFilePolicy::Overwrite),
+            "s" | "skip" => return Some(ExistingFilePolicy::Skip),
+            "r" | "rename" => return Some(ExistingFilePolicy::Rename),
+            "c" | "cancel" => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Runs a headless compression batch using the command line arguments.
+///
+/// Each file is compressed with [`ImageService::compress_single`], exactly as the user
+/// interface does, and a result line is printed per file.
+///
+/// # Arguments
+///
+/// * `args` - The command line arguments, without the program name.
+///
+/// # Returns
+///
+/// The process exit code: `0` when no file failed (skipped files and kept originals are not
+/// failures), `1` when one or more files failed, and `2` for invalid arguments.
+pub fn run(args: Vec<String>) -> i32 {
+    let options = match parse_args(args) {
+        Ok(CliCommand::Compress(options)) => options,
+        Ok(CliCommand::Help) => {
+            println!("{USAGE}");
+            return 0;
+        }
+        Ok(CliCommand::Version) => {
+            println!("compressr {}", env!("CARGO_PKG_VERSION"));
+            return 0;
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return 2;
+        }
+    };
+
+    let (inputs, source_root) = if options.preserve_structure {
+        match common_root(&options.inputs) {
+            Ok((inputs, root)) => (inputs, Some(Arc::from(root.as_path()))),
+            Err(e) => {
+                eprintln!("error: {e}");
+                return 1;
+            }
+        }
+    } else if options.backup_dir.is_some() {
+        // Backups in a shared folder recreate the input folders when they have a common root.
+        match common_root(&options.inputs) {
+            Ok((inputs, root)) => (inputs, Some(Arc::from(root.as_path()))),
+            Err(_) => (options.inputs.clone(), None),
+        }
+    } else {
+        (options.inputs.clone(), None)
+    };
+
+    let files = match collect_files(&inputs, options.recursive) {
+        Ok(files) if files.is_empty() => {
+            eprintln!("error: No image files found in the given inputs");
+            return 1;
+        }
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return 1;
+        }
+    };
+
+    // A single input file may be written to an explicit file path; anything else goes
+    // into an output directory, which is created when missing. In place there is no output.
+    let output_path = options.output.clone().unwrap_or_default();
+    let output = Path::new(&output_path);
+    let is_output_a_directory =
+        options.in_place || output.is_dir() || files.len() > 1 || output.extension().is_none();
+    if !options.in_place
+        && is_output_a_directory
+        && let Err(e) = std::fs::create_dir_all(output)
+    {
+        eprintln!("error: Could not create output directory '{output_path}': {e}");
+        return 1;
+    }
+
+    let in_place_backup = options.in_place.then(|| match &options.backup_dir {
+        Some(dir) => BackupTarget::Directory(Arc::from(Path::new(dir))),
+        None => BackupTarget::Sibling,
+    });
+
+    let mut params = CompressionParams {
+        output_path: Arc::from(output_path.as_str()),
+        is_output_a_directory,
+        scale: options.scale,
+        width: options.width,
+        height: options.height,
+        quality: options.quality,
+        target_size: options.target_size,
+        downscale_to_target_size: options.downscale_to_target_size,
+        min_similarity: options.min_similarity,
+        larger_output_policy: options.larger_output_policy,
+        existing_file_policy: options.existing_file_policy,
+        format_mapping: Arc::new(options.format_mapping),
+        source_root,
+        file_name_template: Arc::new(options.file_name_template),
+        in_place_backup,
+        avif_speed: options.avif_speed,
+        png_optimization_level: options.png_optimization_level,
+        png_zopfli: options.png_zopfli,
+        lossy_png: options.lossy_png,
+        max_frame_rate: options.max_frame_rate,
+        optimize_gif_palette: options.optimize_gif_palette,
+        jpeg_options: options.jpeg_options,
+        format: options.format,
+        preserve_exif: options.preserve_exif,
+        output_path_override: None,
+    };
+
+    let service = ImageService::new();
+    let resolved_paths = service.resolve_unique_output_paths(&files, &params);
+    // In place, existing files are never replaced, so there is nothing to ask.
+    if params.existing_file_policy == ExistingFilePolicy::Ask && params.in_place_backup.is_none() {
+        let existing = resolved_paths
+            .iter()
+            .zip(&files)
+            .filter(|(p, file)| p != file && Path::new(p).exists())
+            .count();
+        if existing > 0 {
+            match prompt_existing_file_policy(existing) {
+                Some(policy) => params.existing_file_policy = policy,
+                None => return 1,
+            }
+        }
+    }
+    let cancelled = Arc::new(AtomicBool::new(false));
+
+    let mut failures = 0;
+    let mut kept = 0;
+    let mut skipped = 0;
+    for (file, out_path) in files.iter().zip(resolved_paths) {
+        let mut p = params.clone();
+        p.output_path_override = Some(out_path);
+        match service.compress_single(file.clone(), &p, Arc::clone(&cancelled)) {
+            Ok(r) => {
+                let quality = r
+                    .chosen_quality
+                    .map(|q| format!(", quality {q}"))
+                    .unwrap_or_default();
+                let similarity = r
+                    .similarity
+                    .map(|s| format!(", similarity {:.2}%", s * 100.0))
+                    .unwrap_or_default();
+                match r.action {
+                    OutputAction::Written => {}
+                    OutputAction::KeptOriginal => kept += 1,
+                    OutputAction::Skipped | OutputAction::SkippedExisting => skipped += 1,
+                }
+                let saved = match r.action {
+                    OutputAction::Written => format_pct(r.percent_saved()),
+                    OutputAction::KeptOriginal => "larger, original kept".to_string(),
+                    OutputAction::Skipped => "larger, skipped".to_string(),
+                    OutputAction::SkippedExisting => "exists, skipped".to_string(),
+                };
+                println!(
+                    "{file} -> {}: {} -> {} ({saved}{quality}{similarity})",
+                    r.output_path.as_deref().unwrap_or("-"),
+                    format_size(r.original_size),
+                    format_size(r.compressed_size),
+                );
+            }
+            Err(e) => {
+                failures += 1;
+                eprintln!("{file}: {e}");
+            }
+        }
+    }
+
+    let mut summary = format!(
+        "{} of {} file(s) compressed",
+        files.len() - failures - kept - skipped,
+        files.len()
+    );
+    for (count, label) in [
+        (kept, "original(s) kept"),
+        (skipped, "skipped"),
+        (failures, "failed"),
+    ] {
+        if count > 0 {
+            summary.push_str(&format!(", {count} {label}"));
+        }
+    }
+    println!("{summary}");
+
+    if failures > 0 { 1 } else { 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn parse(list: &[&str]) -> Result<CliOptions, String> {
+        match parse_args(args(list))? {
+            CliCommand::Compress(options) => Ok(options),
+            CliCommand::Help => Err("help".to_string()),
+            CliCommand::Version => Err("version".to_string()),
+        }
+    }
+
+    /// Creates an empty scratch folder unique to the calling test.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("compressr-cli-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn plain_paths_open_the_user_interface() {
+        assert!(!is_cli_invocation(&[]));
+        assert!(!is_cli_invocation(&args(&["a.png", "photos/b.jpg"])));
+        assert!(!is_cli_invocation(&args(&["-", "a.png"])));
+    }
+
+    #[test]
+    fn recognized_options_start_the_command_line_mode() {
+        assert!(is_cli_invocation(&args(&["a.png", "-o", "out"])));
+        assert!(is_cli_invocation(&args(&["--quality=80", "a.png"])));
+        assert!(is_cli_invocation(&args(&["--help"])));
+        assert!(!is_cli_invocation(&args(&["--unknown", "a.png"])));
+    }
+
+    #[test]
+    fn arguments_after_separator_are_not_options() {
+        assert!(!is_cli_invocation(&args(&["--", "--help"])));
+        assert!(is_cli_invocation(&args(&["--in-place", "--", "--help"])));
+
+        let options = parse(&["-o", "out", "--", "--quality", "-r"]).unwrap();
+        assert_eq!(options.inputs, ["--quality", "-r"]);
+        assert_eq!(options.quality, 100);
+        assert!(!options.recursive);
+    }
+
+    #[test]
+    fn options_take_separate_or_inline_values() {
+        let options = parse(&["--quality", "80", "--output=out", "-f=webp", "a.png"]).unwrap();
+        assert_eq!(options.quality, 80);
+        assert_eq!(options.output.as_deref(), Some("out"));
+        assert_eq!(options.format, OutputFormat::WebP);
+        assert_eq!(options.inputs, ["a.png"]);
+
+        // Only the first `=` separates the name from the value.
+        let options = parse(&["--output=a=b", "a.png"]).unwrap();
+        assert_eq!(options.output.as_deref(), Some("a=b"));
+
+        assert_eq!(
+            parse(&["a.png", "--output"]).unwrap_err(),
+            "Missing value for --output"
+        );
+        assert_eq!(
+            parse(&["-o", "out", "--quality=0", "a.png"]).unwrap_err(),
+            "Value for --quality must be between 1 and 100"
+        );
+        assert_eq!(
+            parse(&["-o", "out", "--bogus", "a.png"]).unwrap_err(),
+            "Unknown option '--bogus'"
+        );
+    }
+
+    #[test]
+    fn help_and_version_win_over_missing_options() {
+        assert!(matches!(
+            parse_args(args(&["--help"])),
+            Ok(CliCommand::Help)
+        ));
+        assert!(matches!(parse_args(args(&["-V"])), Ok(CliCommand::Version)));
+    }
+
+    #[test]
+    fn map_overrides_the_default_mapping() {
+        let options = parse(&[
+            "-o",
+            "out",
+            "-f",
+            "original",
+            "--map",
+            "png=webp",
+            "--map=bmp=jpeg",
+            "a.png",
+        ])
+        .unwrap();
+        assert_eq!(options.format, OutputFormat::Original);
+        assert_eq!(
+            options.format_mapping.get(&OutputFormat::Png),
+            Some(&OutputFormat::WebP)
+        );
+        assert_eq!(
+            options.format_mapping.get(&OutputFormat::Bmp),
+            Some(&OutputFormat::Jpeg)
+        );
+
+        for invalid in ["png", "png=original", "original=png"] {
+            assert_eq!(
+                parse(&["-o", "out", "--map", invalid, "a.png"]).unwrap_err(),
+                format!("Invalid value '{invalid}' for --map")
+            );
+        }
+    }
+
+    #[test]
+    fn output_and_in_place_are_exclusive() {
+        assert_eq!(
+            parse(&["a.png"]).unwrap_err(),
+            "Missing required option --output"
+        );
+        assert_eq!(
+            parse(&["-o", "out", "--in-place", "a.png"]).unwrap_err(),
+            "--output cannot be combined with --in-place"
+        );
+        assert_eq!(
+            parse(&["-o", "out", "--backup-dir", "backup", "a.png"]).unwrap_err(),
+            "--backup-dir requires --in-place"
+        );
+        assert_eq!(
+            parse(&["--in-place"]).unwrap_err(),
+            "No input files or folders specified"
+        );
+
+        let options = parse(&["--in-place", "--backup-dir", "backup", "a.png"]).unwrap();
+        assert!(options.in_place);
+        assert_eq!(options.output, None);
+        assert_eq!(options.backup_dir.as_deref(), Some("backup"));
+    }
+
+    #[test]
+    fn min_similarity_is_rejected_for_avif() {
+        assert_eq!(
+            parse(&["-o", "out", "-f", "avif", "--min-similarity", "95", "a.png"]).unwrap_err(),
+            "--min-similarity is not supported for AVIF output"
+        );
+        assert!(
+            parse(&[
+                "-o",
+                "out",
+                "-f",
+                "original",
+                "--map",
+                "png=avif",
+                "--min-similarity",
+                "95",
+                "a.png",
+            ])
+            .is_err()
+        );
+
+        let options = parse(&["-o", "out", "--min-similarity=95", "a.png"]).unwrap();
+        assert_eq!(options.min_similarity, Some(0.95));
+    }
+
+    #[test]
+    fn common_root_is_the_deepest_shared_folder() {
+        let dir = scratch_dir("common-root");
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+        std::fs::create_dir_all(dir.join("a/c")).unwrap();
+        std::fs::write(dir.join("a/b/one.png"), []).unwrap();
+        std::fs::write(dir.join("a/c/two.png"), []).unwrap();
+        let path = |p: &str| dir.join(p).to_string_lossy().into_owned();
+
+        let (canonical, root) = common_root(&[path("a/b/one.png")]).unwrap();
+        assert_eq!(canonical, [path("a/b/one.png")]);
+        assert_eq!(root, dir.join("a/b"));
+
+        let (_, root) = common_root(&[path("a/b/one.png"), path("a/c/two.png")]).unwrap();
+        assert_eq!(root, dir.join("a"));
+
+        // A folder input is its own root rather than its parent's.
+        let (_, root) = common_root(&[path("a/b"), path("a/b/one.png")]).unwrap();
+        assert_eq!(root, dir.join("a/b"));
+
+        // Components are compared whole, so `a/b` does not share `a/bc`.
+        std::fs::create_dir_all(dir.join("a/bc")).unwrap();
+        let (_, root) = common_root(&[path("a/b"), path("a/bc")]).unwrap();
+        assert_eq!(root, dir.join("a"));
+
+        let missing = path("a/missing.png");
+        assert!(
+            common_root(std::slice::from_ref(&missing))
+                .unwrap_err()
+                .starts_with(&format!("Input '{missing}' could not be resolved"))
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
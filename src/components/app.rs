@@ -1,9 +1,16 @@
+use crate::components::settings::{BackupLocation, DeleteMode, backup_root, journal_path};
 use crate::components::state::State;
 use crate::components::window::{Window, WindowKind, load_app_icon, make_window_settings};
 use crate::services;
-use crate::services::folder_scanner::{IMAGE_EXTENSIONS, scan_folder};
+use crate::services::batch_journal::{BatchJournal, BatchSettings};
+use crate::services::compression_queue::{
+    CompressionJob, CompressionQueue, ResolvedBatch, resolve_batch, source_pixels,
+};
+use crate::services::file_name_template::FileNameTemplate;
+use crate::services::folder_scanner::{IMAGE_EXTENSIONS, ScanResult, scan_folder};
 use crate::services::image_service::{
-    CompressionParams, CompressionResult, ImageService, OutputFormat,
+    BackupTarget, CompressionParams, CompressionResult, ExistingFilePolicy, ImageService,
+    JpegOptions, LargerOutputPolicy, OutputFormat, SourceInfo,
 };
 use crate::services::update_service::{UpdateInfo, UpdateService};
 use iced::widget::space;
@@ -11,8 +18,10 @@ use iced::{Element, Subscription, Task, Theme, clipboard, window};
 use log::{error, info};
 use rfd::FileDialog;
 use std::collections::BTreeMap;
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -24,22 +33,53 @@ pub enum Message {
     SelectInputFolder,
     ToggleInputDropdown,
     DismissInputDropdown,
+    ToggleJpegOptionsDropdown,
+    DismissJpegOptionsDropdown,
+    JpegOptionsChanged(JpegOptions),
     Compress,
-    SingleFileCompressed(Result<CompressionResult, String>),
+    OutputPathsResolved(Result<ResolvedBatch, String>),
+    ExistingFilesResolved(Option<ExistingFilePolicy>),
+    SingleFileCompressed(usize, Result<CompressionResult, String>),
+    PixelsCounted(Vec<u64>),
+    CancelCompression,
+    PauseCompression,
+    ResumeCompression,
+    ProgressTick,
+    InterruptedBatchResolved(bool),
     CloseResultsView,
-    InputFolderScanCompleted(Vec<String>),
+    InputFolderScanCompleted(ScanResult),
     InputFolderScanFailed(String),
+    FileNamePreviewSourceRead(String, SourceInfo),
     FormatSelected(OutputFormat),
     QualityChanged(u8),
     WidthChanged(i32),
     HeightChanged(i32),
+    TargetSizeChanged(i32),
+    MinSimilarityChanged(f32),
     CompressionScaleChanged(u32),
     Noop,
     AutoUpdateToggled(bool),
     DeleteFilesAfterCompressionToggled(bool),
+    DeleteModeChanged(DeleteMode),
+    InPlaceToggled(bool),
+    BackupLocationChanged(BackupLocation),
     PreserveExifToggled(bool),
     ShowCompressionResultsToggled(bool),
     RecursiveFolderScanToggled(bool),
+    PreserveFolderStructureToggled(bool),
+    AvifSpeedChanged(u8),
+    PngOptimizationLevelChanged(u8),
+    PngZopfliToggled(bool),
+    LossyPngToggled(bool),
+    MaxFrameRateChanged(i32),
+    OptimizeGifPaletteToggled(bool),
+    MaxConcurrentJobsChanged(i32),
+    PixelBudgetChanged(i32),
+    DownscaleToTargetSizeToggled(bool),
+    LargerOutputPolicyChanged(LargerOutputPolicy),
+    ExistingFilePolicyChanged(ExistingFilePolicy),
+    FormatMappingChanged(OutputFormat, OutputFormat),
+    FileNameTemplateChanged(String),
     ThemeChanged(Theme),
     ResetSettings,
     LanguageChanged(String),
@@ -79,10 +119,15 @@ pub struct App {
 impl App {
     /// Initialize a new App with an empty window map and default state, then open the main view.
     ///
+    /// # Arguments
+    ///
+    /// * `files` - Paths passed on the command line, e.g. by "Open with"; existing image files
+    ///   among them become the input.
+    ///
     /// # Returns
     ///
     /// A tuple of the new App instance and a Task that opens the main view when executed by iced.
-    pub fn new() -> (Self, Task<Message>) {
+    pub fn new(files: Vec<String>) -> (Self, Task<Message>) {
         info!("Initializing new App");
 
         let icon = load_app_icon();
@@ -102,15 +147,38 @@ impl App {
             state.last_error_message = Some(e.to_string());
         }
 
+        match BatchJournal::load(journal_path()) {
+            Ok(journal) => state.interrupted_batch = journal,
+            Err(e) => {
+                error!("Discarding unreadable batch journal: {e}");
+                if let Err(e) = BatchJournal::remove(journal_path()) {
+                    error!("{e}");
+                }
+            }
+        }
+
+        state.input_path = files
+            .into_iter()
+            .filter(|file| {
+                let path = Path::new(file);
+                path.is_file()
+                    && path.extension().is_some_and(|ext| {
+                        IMAGE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+                    })
+            })
+            .collect();
+
+        let mut app = Self {
+            windows: BTreeMap::new(),
+            state,
+            image_service: ImageService::new(),
+            update_service: UpdateService::new(update_server),
+            icon,
+        };
+        let read_preview_source = app.read_file_name_preview_source();
         (
-            Self {
-                windows: BTreeMap::new(),
-                state,
-                image_service: ImageService::new(),
-                update_service: UpdateService::new(update_server),
-                icon,
-            },
-            open.map(Message::MainViewOpened),
+            app,
+            Task::batch([open.map(Message::MainViewOpened), read_preview_source]),
         )
     }
 
@@ -162,7 +230,16 @@ impl App {
     ///
     /// A subscription that listens for window close events and produces `Message::WindowClosed` messages when they occur.
     pub fn subscription(&self) -> Subscription<Message> {
-        window::close_events().map(Message::WindowClosed)
+        let close_events = window::close_events().map(Message::WindowClosed);
+        // Refreshes the elapsed time and estimates in the main view while files are compressed.
+        if self.state.is_compressing && !self.state.compression_queue.is_paused() {
+            Subscription::batch([
+                close_events,
+                iced::time::every(Duration::from_secs(1)).map(|_| Message::ProgressTick),
+            ])
+        } else {
+            close_events
+        }
     }
 
     /// Returns the view for the window with the given ID, or an empty space if no such window exists.
@@ -254,6 +331,8 @@ impl App {
                     .pick_files()
                 {
                     self.state.input_path = paths.iter().map(|p| p.display().to_string()).collect();
+                    self.state.input_root = None;
+                    return self.read_file_name_preview_source();
                 }
                 Task::none()
             }
@@ -270,7 +349,7 @@ impl App {
                     return Task::perform(
                         tokio::task::spawn_blocking(move || scan_folder(folder, recursive)),
                         |result| match result {
-                            Ok(Ok(files)) => Message::InputFolderScanCompleted(files),
+                            Ok(Ok(scan)) => Message::InputFolderScanCompleted(scan),
                             Ok(Err(e)) => Message::InputFolderScanFailed(e),
                             Err(e) => Message::InputFolderScanFailed(format!(
                                 "Folder scan task failed: {e}"
@@ -280,8 +359,16 @@ impl App {
                 }
                 Task::none()
             }
-            Message::InputFolderScanCompleted(files) => {
-                self.state.input_path = files;
+            Message::InputFolderScanCompleted(scan) => {
+                self.state.input_path = scan.paths();
+                self.state.input_root = Some(scan.root);
+                self.read_file_name_preview_source()
+            }
+            Message::FileNamePreviewSourceRead(file, info) => {
+                if self.state.input_path.first() == Some(&file) {
+                    self.state.file_name_preview_source = Some((file, info));
+                    self.refresh_file_name_preview();
+                }
                 Task::none()
             }
             Message::InputFolderScanFailed(errors) => self.error(errors),
@@ -289,31 +376,61 @@ impl App {
                 if let Err(msg) = self.validate_compression_inputs() {
                     return self.error(msg);
                 }
-
-                self.state.is_compressing = true;
-                self.state
-                    .compression_aborted
-                    .store(false, Ordering::Relaxed);
-                self.state.show_input_dropdown = false;
-                self.state.compression_results = Vec::new();
-                self.state.last_error_message = None;
+                let params = match self.compression_params() {
+                    Ok(params) => params,
+                    Err(msg) => return self.error(msg),
+                };
 
                 let input = self.state.input_path.clone();
-                let params = CompressionParams {
-                    output_path: Arc::from(self.state.output_path.as_str()),
-                    is_output_a_directory: true,
-                    scale: self.state.scale,
-                    width: self.state.width,
-                    height: self.state.height,
-                    quality: self.state.quality,
-                    format: self.state.format,
-                    preserve_exif: self.state.settings.preserve_exif,
-                    output_path_override: None,
+                self.reset_progress(input.len());
+                let svc = self.image_service.clone();
+                Task::perform(
+                    tokio::task::spawn_blocking(move || resolve_batch(&svc, input, params)),
+                    |result| {
+                        Message::OutputPathsResolved(
+                            result.map_err(|e| format!("Output path resolution failed: {e}")),
+                        )
+                    },
+                )
+            }
+            Message::OutputPathsResolved(result) => {
+                // The batch was cancelled, or the app is exiting, while its paths were resolved.
+                if self.state.compression_aborted.load(Ordering::Relaxed) {
+                    return Task::none();
+                }
+                let batch = match result {
+                    Ok(batch) => batch,
+                    Err(e) => {
+                        self.state.is_compressing = false;
+                        return self.error(e);
+                    }
                 };
-                self.state.progress_total = input.len();
-                self.state.progress_completed = 0;
+                if batch.existing > 0 {
+                    self.state.is_compressing = false;
+                    self.state.show_input_dropdown = false;
+                    self.state.existing_files_prompt = Some(batch.existing);
+                    self.state.pending_batch = Some(batch);
+                    return Task::none();
+                }
 
-                self.spawn_compression_tasks(input, params)
+                self.start_batch(batch.jobs, batch.params)
+            }
+            Message::ExistingFilesResolved(policy) => {
+                self.state.existing_files_prompt = None;
+                let Some(batch) = self.state.pending_batch.take() else {
+                    return Task::none();
+                };
+                let Some(policy) = policy else {
+                    return Task::none();
+                };
+                self.reset_progress(batch.jobs.len());
+                self.start_batch(
+                    batch.jobs,
+                    CompressionParams {
+                        existing_file_policy: policy,
+                        ..batch.params
+                    },
+                )
             }
             Message::ToggleInputDropdown => {
                 self.state.show_input_dropdown = !self.state.show_input_dropdown;
@@ -323,13 +440,97 @@ impl App {
                 self.state.show_input_dropdown = false;
                 Task::none()
             }
-            Message::SingleFileCompressed(result) => {
-                if self.state.compression_aborted.load(Ordering::Relaxed) {
+            Message::ToggleJpegOptionsDropdown => {
+                self.state.show_jpeg_options_dropdown = !self.state.show_jpeg_options_dropdown;
+                Task::none()
+            }
+            Message::DismissJpegOptionsDropdown => {
+                self.state.show_jpeg_options_dropdown = false;
+                Task::none()
+            }
+            Message::JpegOptionsChanged(options) => {
+                self.state.jpeg_options = options;
+                Task::none()
+            }
+            Message::PixelsCounted(counts) => {
+                if self.state.compression_aborted.load(Ordering::Relaxed)
+                    && !self.state.is_cancelling
+                {
                     return Task::none();
                 }
+                self.state.compression_queue.set_pixel_counts(counts);
+                if self.state.is_cancelling {
+                    return self.on_compression_cancelled();
+                }
+                self.start_ready_jobs()
+            }
+            Message::CancelCompression => {
+                if !self.state.is_compressing || self.state.is_cancelling {
+                    return Task::none();
+                }
+                info!("Cancelling compression");
+                self.state.is_cancelling = true;
+                self.state
+                    .compression_aborted
+                    .store(true, Ordering::Relaxed);
+                self.state.compression_queue.clear_pending();
+                if self.state.compression_queue.has_running() {
+                    Task::none()
+                } else {
+                    self.on_compression_cancelled()
+                }
+            }
+            Message::PauseCompression => {
+                if self.state.is_compressing && !self.state.is_cancelling {
+                    info!("Pausing compression");
+                    self.state.compression_queue.set_paused(true);
+                    self.state.paused_since.get_or_insert_with(Instant::now);
+                }
+                Task::none()
+            }
+            Message::ResumeCompression => {
+                if !self.state.is_compressing || self.state.is_cancelling {
+                    return Task::none();
+                }
+                info!("Resuming compression");
+                self.state.compression_queue.set_paused(false);
+                if let Some(since) = self.state.paused_since.take() {
+                    self.state.progress_paused += since.elapsed();
+                }
+                self.start_ready_jobs()
+            }
+            Message::ProgressTick => Task::none(),
+            Message::InterruptedBatchResolved(resume) => {
+                let Some(journal) = self.state.interrupted_batch.take() else {
+                    return Task::none();
+                };
+                if resume {
+                    self.resume_batch(journal)
+                } else {
+                    info!("Discarding interrupted batch");
+                    if let Err(e) = BatchJournal::remove(journal_path()) {
+                        error!("{e}");
+                    }
+                    Task::none()
+                }
+            }
+            Message::SingleFileCompressed(id, result) => {
+                // Without a cancel request, the flag is only set while the app is exiting.
+                if self.state.compression_aborted.load(Ordering::Relaxed)
+                    && !self.state.is_cancelling
+                {
+                    return Task::none();
+                }
+                self.state.compression_queue.finish(id);
                 self.state.progress_completed += 1;
+                if !matches!(&result, Err(e) if e == "COMPRESSION_ABORTED")
+                    && let Err(e) = BatchJournal::record_finished(journal_path(), id)
+                {
+                    error!("{e}");
+                }
                 match result {
                     Ok(r) => self.state.compression_results.push(r),
+                    Err(e) if e == "COMPRESSION_ABORTED" => {}
                     Err(e) => {
                         error!("Compression error: {e}");
                         let msg = if let Some(ref existing) = self.state.last_error_message {
@@ -341,33 +542,57 @@ impl App {
                     }
                 }
 
+                if self.state.is_cancelling {
+                    if self.state.compression_queue.has_running() {
+                        return Task::none();
+                    }
+                    return self.on_compression_cancelled();
+                }
                 if self.state.progress_completed == self.state.progress_total {
                     self.state.is_compressing = false;
+                    self.state.batch_params = None;
+                    if let Err(e) = BatchJournal::remove(journal_path()) {
+                        error!("{e}");
+                    }
                     return self.on_compression_complete();
                 }
-                Task::none()
+                self.start_ready_jobs()
             }
             Message::FormatSelected(f) => {
                 self.state.format = f;
-                if f != OutputFormat::Jpeg && f != OutputFormat::WebP {
+                // PNG stays lossless at its default quality unless lossy PNG is switched on.
+                if !f.supports_quality(self.state.settings.lossy_png) || f == OutputFormat::Png {
                     self.state.quality = 100;
                 }
+                self.refresh_file_name_preview();
                 Task::none()
             }
             Message::QualityChanged(q) => {
                 self.state.quality = q;
+                self.refresh_file_name_preview();
                 Task::none()
             }
             Message::WidthChanged(w) => {
                 self.state.width = (w > 0).then_some(w as u32);
+                self.refresh_file_name_preview();
                 Task::none()
             }
             Message::HeightChanged(h) => {
                 self.state.height = (h > 0).then_some(h as u32);
+                self.refresh_file_name_preview();
+                Task::none()
+            }
+            Message::TargetSizeChanged(kib) => {
+                self.state.target_size_kib = (kib > 0).then_some(kib as u32);
+                Task::none()
+            }
+            Message::MinSimilarityChanged(pct) => {
+                self.state.min_similarity = (pct > 0.0).then_some(pct);
                 Task::none()
             }
             Message::CompressionScaleChanged(s) => {
                 self.state.scale = s;
+                self.refresh_file_name_preview();
                 Task::none()
             }
             Message::Noop => Task::none(),
@@ -375,6 +600,13 @@ impl App {
             Message::DeleteFilesAfterCompressionToggled(v) => {
                 settings_toggle!(self, delete_files_after_compression, v)
             }
+            Message::DeleteModeChanged(v) => settings_toggle!(self, delete_mode, v),
+            Message::InPlaceToggled(v) => {
+                self.state.settings.in_place = v;
+                self.refresh_file_name_preview();
+                self.handle_settings_save_result(self.state.settings.save())
+            }
+            Message::BackupLocationChanged(v) => settings_toggle!(self, backup_location, v),
             Message::PreserveExifToggled(v) => settings_toggle!(self, preserve_exif, v),
             Message::ShowCompressionResultsToggled(v) => {
                 settings_toggle!(self, show_compression_results, v)
@@ -382,6 +614,46 @@ impl App {
             Message::RecursiveFolderScanToggled(v) => {
                 settings_toggle!(self, recursive_folder_scan, v)
             }
+            Message::PreserveFolderStructureToggled(v) => {
+                settings_toggle!(self, preserve_folder_structure, v)
+            }
+            Message::AvifSpeedChanged(v) => settings_toggle!(self, avif_speed, v),
+            Message::PngOptimizationLevelChanged(v) => {
+                settings_toggle!(self, png_optimization_level, v)
+            }
+            Message::PngZopfliToggled(v) => settings_toggle!(self, png_zopfli, v),
+            Message::LossyPngToggled(v) => settings_toggle!(self, lossy_png, v),
+            Message::MaxFrameRateChanged(v) => {
+                settings_toggle!(self, max_frame_rate, (v > 0).then_some(v as u32))
+            }
+            Message::OptimizeGifPaletteToggled(v) => {
+                settings_toggle!(self, optimize_gif_palette, v)
+            }
+            Message::MaxConcurrentJobsChanged(v) => {
+                settings_toggle!(self, max_concurrent_jobs, v.max(1) as u32)
+            }
+            Message::PixelBudgetChanged(v) => {
+                settings_toggle!(self, pixel_budget_megapixels, (v > 0).then_some(v as u32))
+            }
+            Message::DownscaleToTargetSizeToggled(v) => {
+                settings_toggle!(self, downscale_to_target_size, v)
+            }
+            Message::LargerOutputPolicyChanged(v) => {
+                settings_toggle!(self, larger_output_policy, v)
+            }
+            Message::ExistingFilePolicyChanged(v) => {
+                settings_toggle!(self, existing_file_policy, v)
+            }
+            Message::FormatMappingChanged(source, target) => {
+                self.state.settings.format_mapping.insert(source, target);
+                self.refresh_file_name_preview();
+                self.handle_settings_save_result(self.state.settings.save())
+            }
+            Message::FileNameTemplateChanged(template) => {
+                self.state.settings.file_name_template = template;
+                self.refresh_file_name_preview();
+                self.handle_settings_save_result(self.state.settings.save())
+            }
             Message::ThemeChanged(theme) => {
                 self.state.settings.theme = theme.clone();
                 self.windows
@@ -396,6 +668,7 @@ impl App {
                 self.windows
                     .values_mut()
                     .for_each(|w| w.theme = theme.clone());
+                self.refresh_file_name_preview();
                 self.handle_settings_save_result(self.state.settings.save())
             }
             Message::LanguageChanged(new_language) => {
@@ -613,6 +886,124 @@ impl App {
         Task::done(Message::OpenErrorView)
     }
 
+    /// Builds the compression parameters for a batch from the current state and settings.
+    ///
+    /// # Returns
+    ///
+    /// The compression parameters, or an error message if the file name template is invalid.
+    fn compression_params(&self) -> Result<CompressionParams, String> {
+        let file_name_template = FileNameTemplate::parse(&self.state.settings.file_name_template)?;
+
+        Ok(CompressionParams {
+            output_path: Arc::from(self.state.output_path.as_str()),
+            is_output_a_directory: true,
+            scale: self.state.scale,
+            width: self.state.width,
+            height: self.state.height,
+            quality: self.state.quality,
+            lossy_png: self.state.settings.lossy_png,
+            avif_speed: self.state.settings.avif_speed,
+            png_optimization_level: self.state.settings.png_optimization_level,
+            png_zopfli: self.state.settings.png_zopfli,
+            max_frame_rate: self.state.settings.max_frame_rate,
+            optimize_gif_palette: self.state.settings.optimize_gif_palette,
+            jpeg_options: self.state.jpeg_options,
+            target_size: self.state.target_size_kib.map(|kib| kib as u64 * 1024),
+            downscale_to_target_size: self.state.settings.downscale_to_target_size,
+            min_similarity: self.state.min_similarity.map(|pct| pct as f64 / 100.0),
+            larger_output_policy: self.state.settings.larger_output_policy,
+            existing_file_policy: self.state.settings.existing_file_policy,
+            format_mapping: Arc::new(self.state.settings.format_mapping.clone()),
+            source_root: self
+                .state
+                .input_root
+                .as_deref()
+                .filter(|_| {
+                    self.state.settings.preserve_folder_structure || self.state.settings.in_place
+                })
+                .map(Arc::from),
+            file_name_template: Arc::new(file_name_template),
+            in_place_backup: self.state.settings.in_place.then(|| {
+                match self.state.settings.backup_location {
+                    BackupLocation::Timestamped => BackupTarget::Directory(Arc::from(
+                        backup_root()
+                            .join(chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string())
+                            .as_path(),
+                    )),
+                    BackupLocation::Sibling => BackupTarget::Sibling,
+                }
+            }),
+            format: self.state.format,
+            preserve_exif: self.state.settings.preserve_exif,
+            output_path_override: None,
+        })
+    }
+
+    /// Renders the output file name of the first input file for the preview in the main view.
+    ///
+    /// The name is rendered from the cached [`SourceInfo`] of the first input without touching
+    /// the file system, so it can run on every slider tick and keystroke. The preview is
+    /// cleared while that information is being read or no input is selected, and shows the
+    /// template error when the template is invalid.
+    fn refresh_file_name_preview(&mut self) {
+        let Some((file, info)) = self
+            .state
+            .file_name_preview_source
+            .as_ref()
+            .filter(|(file, _)| self.state.input_path.first() == Some(file))
+        else {
+            self.state.file_name_preview = None;
+            return;
+        };
+
+        self.state.file_name_preview = Some(match self.compression_params() {
+            Ok(params) => {
+                let path = self
+                    .image_service
+                    .render_output_path(file, 0, &params, info);
+                Path::new(&path)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or(path)
+            }
+            Err(e) => e,
+        });
+    }
+
+    /// Reads what the output name of the first input file depends on, off the UI thread.
+    ///
+    /// Everything is read once per selection, so later setting changes re-render the preview
+    /// from the cache. The result arrives through [`Message::FileNamePreviewSourceRead`].
+    ///
+    /// # Returns
+    ///
+    /// A task reading the source information, or `Task::none()` when it is already cached or
+    /// no input is selected.
+    fn read_file_name_preview_source(&mut self) -> Task<Message> {
+        self.refresh_file_name_preview();
+        let Some(file) = self.state.input_path.first().cloned() else {
+            return Task::none();
+        };
+        if self.state.file_name_preview.is_some() {
+            return Task::none();
+        }
+
+        let svc = self.image_service.clone();
+        Task::perform(
+            tokio::task::spawn_blocking(move || {
+                let info = svc.read_source_info(&file, None);
+                (file, info)
+            }),
+            |result| match result {
+                Ok((file, info)) => Message::FileNamePreviewSourceRead(file, info),
+                Err(e) => {
+                    error!("Reading the file name preview source failed: {e}");
+                    Message::Noop
+                }
+            },
+        )
+    }
+
     /// Validates that compression inputs are present and the output directory exists.
     ///
     /// # Returns
@@ -622,6 +1013,10 @@ impl App {
         if self.state.input_path.is_empty() {
             return Err("No input files selected. Please select at least one file.".to_string());
         }
+        // In-place compression writes next to the inputs.
+        if self.state.settings.in_place {
+            return Ok(());
+        }
         if self.state.output_path.is_empty() {
             return Err(
                 "No output directory selected. Please select an output directory.".to_string(),
@@ -639,70 +1034,271 @@ impl App {
         }
     }
 
-    /// Builds a batched [`Task`] that compresses every input file off the GUI thread.
+    /// Marks a new batch as running and clears the progress and results of the previous one.
+    ///
+    /// # Arguments
+    ///
+    /// * `total` - The number of files in the new batch.
+    fn reset_progress(&mut self, total: usize) {
+        self.state.is_compressing = true;
+        self.state
+            .compression_aborted
+            .store(false, Ordering::Relaxed);
+        self.state.show_input_dropdown = false;
+        self.state.show_jpeg_options_dropdown = false;
+        self.state.compression_results = Vec::new();
+        self.state.last_error_message = None;
+        self.state.cancelled_summary = None;
+        self.state.progress_total = total;
+        self.state.progress_completed = 0;
+        self.state.progress_started = Some((Instant::now(), 0));
+        self.state.progress_paused = Duration::ZERO;
+        self.state.paused_since = None;
+    }
+
+    /// Journals a new batch and starts its first jobs.
+    ///
+    /// The jobs carry output paths pre-resolved and de-duplicated by [`resolve_batch`]. They run
+    /// through a [`CompressionQueue`] bounded by the configured concurrency and pixel budget.
+    ///
+    /// # Arguments
+    ///
+    /// * `jobs` - The jobs to run, in input order.
+    /// * `params` - The compression parameters shared across the batch.
+    ///
+    /// # Returns
+    ///
+    /// A `Task<Message>` batching the compression tasks that may start right away.
+    fn start_batch(
+        &mut self,
+        jobs: Vec<CompressionJob>,
+        params: CompressionParams,
+    ) -> Task<Message> {
+        let settings = BatchSettings {
+            delete_files_after_compression: self.state.settings.delete_files_after_compression,
+            in_place: self.state.settings.in_place,
+        };
+        if let Err(e) = BatchJournal::create(journal_path(), &params, settings, &jobs) {
+            error!("{e}");
+        }
+
+        self.run_jobs(jobs, params, settings)
+    }
+
+    /// Resumes a batch that was interrupted by a crash or restart.
+    ///
+    /// Only the files the journal does not record as finished are compressed again, using the
+    /// output paths, parameters and delete settings of the original batch. Originals of files completed before
+    /// the interruption are not deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `journal` - The journal of the interrupted batch.
+    ///
+    /// # Returns
+    ///
+    /// A `Task<Message>` batching the compression tasks that may start right away.
+    fn resume_batch(&mut self, journal: BatchJournal) -> Task<Message> {
+        let jobs = journal.remaining_jobs();
+        info!(
+            "Resuming interrupted batch with {} of {} file(s) remaining",
+            jobs.len(),
+            journal.files.len()
+        );
+        self.reset_progress(journal.files.len());
+        self.state.progress_completed = journal.finished.len();
+        self.state.progress_started = Some((Instant::now(), journal.finished.len()));
+        if jobs.is_empty() {
+            self.state.is_compressing = false;
+            if let Err(e) = BatchJournal::remove(journal_path()) {
+                error!("{e}");
+            }
+            return Task::none();
+        }
+
+        self.run_jobs(jobs, journal.params, journal.settings)
+    }
+
+    /// Queues jobs and starts the first ones.
     ///
-    /// Output paths are pre-resolved and de-duplicated up front via
-    /// [`ImageService::resolve_unique_output_paths`], then each file is compressed in its own
-    /// `spawn_blocking` task. Results are reported back through [`Message::SingleFileCompressed`].
+    /// With a pixel budget, the pixel counts of the jobs are read in a `spawn_blocking` task
+    /// first and reported back through [`Message::PixelsCounted`].
     ///
     /// # Arguments
     ///
-    /// * `input` - The input file paths to compress.
+    /// * `jobs` - The jobs to run.
     /// * `params` - The compression parameters shared across the batch.
+    /// * `batch_settings` - The settings that apply once the batch has finished.
     ///
     /// # Returns
     ///
-    /// A `Task<Message>` batching one compression task per input file.
-    fn spawn_compression_tasks(
-        &self,
-        input: Vec<String>,
+    /// A `Task<Message>` batching the compression tasks that may start right away, or the task
+    /// counting the pixels.
+    fn run_jobs(
+        &mut self,
+        jobs: Vec<CompressionJob>,
         params: CompressionParams,
+        batch_settings: BatchSettings,
     ) -> Task<Message> {
-        let resolved_paths = self
-            .image_service
-            .resolve_unique_output_paths(&input, &params);
+        let settings = &self.state.settings;
+        self.state.compression_queue = CompressionQueue::new(
+            jobs,
+            settings.max_concurrent_jobs as usize,
+            settings
+                .pixel_budget_megapixels
+                .map(|mp| u64::from(mp) * 1_000_000),
+        );
+        self.state.batch_params = Some(params);
+        self.state.batch_settings = batch_settings;
+
+        match self.state.compression_queue.inputs_to_count() {
+            Some(inputs) => Task::perform(
+                tokio::task::spawn_blocking(move || {
+                    inputs.iter().map(|input| source_pixels(input)).collect()
+                }),
+                |result| Message::PixelsCounted(result.unwrap_or_default()),
+            ),
+            None => self.start_ready_jobs(),
+        }
+    }
 
-        let tasks: Vec<Task<Message>> = input
+    /// Starts every queued job that fits within the concurrency and pixel limits.
+    ///
+    /// Each job is compressed in its own `spawn_blocking` task. Results are reported back
+    /// through [`Message::SingleFileCompressed`].
+    ///
+    /// # Returns
+    ///
+    /// A `Task<Message>` batching one compression task per started job.
+    fn start_ready_jobs(&mut self) -> Task<Message> {
+        let Some(params) = self.state.batch_params.as_ref() else {
+            return Task::none();
+        };
+
+        let tasks: Vec<Task<Message>> = self
+            .state
+            .compression_queue
+            .start_ready()
             .into_iter()
-            .zip(resolved_paths)
-            .map(|(file, out_path)| {
-                let svc = self.image_service.clone();
-                let mut p = params.clone();
-                let cancelled = Arc::clone(&self.state.compression_aborted);
-                p.output_path_override = Some(out_path);
-                Task::perform(
-                    tokio::task::spawn_blocking(move || svc.compress_single(file, &p, cancelled)),
-                    |result| match result {
-                        Ok(r) => Message::SingleFileCompressed(r),
-                        Err(e) => Message::SingleFileCompressed(Err(format!(
-                            "Compression task failed: {e}"
-                        ))),
-                    },
-                )
-            })
+            .map(
+                |CompressionJob {
+                     id,
+                     input,
+                     output_path,
+                     ..
+                 }| {
+                    let svc = self.image_service.clone();
+                    let mut p = params.clone();
+                    let cancelled = Arc::clone(&self.state.compression_aborted);
+                    p.output_path_override = Some(output_path);
+                    Task::perform(
+                        tokio::task::spawn_blocking(move || {
+                            svc.compress_single(input, &p, cancelled)
+                        }),
+                        move |result| match result {
+                            Ok(r) => Message::SingleFileCompressed(id, r),
+                            Err(e) => Message::SingleFileCompressed(
+                                id,
+                                Err(format!("Compression task failed: {e}")),
+                            ),
+                        },
+                    )
+                },
+            )
             .collect();
 
         Task::batch(tasks)
     }
 
+    /// Removes an original file after compression according to the configured delete mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The original file to remove.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the file is in the trash or deleted, or an error message on failure.
+    fn remove_original(&self, file: &str) -> Result<(), String> {
+        match self.state.settings.delete_mode {
+            DeleteMode::Trash => trash::delete(file).map_err(|e| e.to_string()),
+            DeleteMode::Permanent => std::fs::remove_file(file).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Finishes a cancelled batch once its in-flight files have stopped.
+    ///
+    /// Files that were completed before the cancellation keep their outputs, but their originals
+    /// are never deleted; aborted files leave no output behind. A summary of the completed files
+    /// is shown in the main view, and any errors or results are shown as after a full batch.
+    ///
+    /// # Returns
+    ///
+    /// A task that opens the error or results view, if needed.
+    fn on_compression_cancelled(&mut self) -> Task<Message> {
+        if let Err(e) = BatchJournal::remove(journal_path()) {
+            error!("{e}");
+        }
+        self.state.is_compressing = false;
+        self.state.is_cancelling = false;
+        self.state.batch_params = None;
+        self.state.cancelled_summary = Some((
+            self.state.compression_results.len(),
+            self.state.progress_total,
+        ));
+        info!(
+            "Compression cancelled after {} of {} file(s)",
+            self.state.compression_results.len(),
+            self.state.progress_total
+        );
+
+        if self.state.last_error_message.is_some() {
+            return Task::done(Message::OpenErrorView);
+        }
+        if self.state.settings.show_compression_results
+            && !self.state.compression_results.is_empty()
+        {
+            self.open_window(WindowKind::Results)
+        } else {
+            Task::none()
+        }
+    }
+
     /// Handles the post-compression completion: error display, original file deletion, and results view.
     ///
     /// # Returns
     ///
     /// A task that manages the post-compression actions.
     fn on_compression_complete(&mut self) -> Task<Message> {
-        let has_errors = self.state.last_error_message.is_some();
-        if has_errors {
-            return Task::done(Message::OpenErrorView);
+        // Only originals whose output was written are removed; failed, skipped and kept
+        // files stay untouched. In-place compression already replaced the originals, keeping
+        // backups. The settings of the batch apply, which a resumed batch read from its journal.
+        if self.state.batch_settings.deletes_originals() {
+            let failures: Vec<String> = self
+                .state
+                .compression_results
+                .iter()
+                .filter(|r| r.can_remove_original())
+                .filter_map(|r| {
+                    let file = &r.source_path;
+                    self.remove_original(file).err().map(|e| {
+                        error!("Failed to delete original file '{file}': {e}");
+                        format!("Failed to delete original file '{file}': {e}")
+                    })
+                })
+                .collect();
+            if !failures.is_empty() {
+                let failures = failures.join("\n");
+                let msg = match self.state.last_error_message.take() {
+                    Some(existing) => format!("{existing}\n{failures}"),
+                    None => failures,
+                };
+                self.set_error(msg);
+            }
         }
 
-        if self.state.settings.delete_files_after_compression {
-            for file in &self.state.input_path {
-                if let Err(e) = std::fs::remove_file(file) {
-                    error!("Failed to delete original file '{file}': {e}");
-                    return self.error(format!("Failed to delete original file '{file}': {e}"));
-                }
-            }
+        if self.state.last_error_message.is_some() {
+            return Task::done(Message::OpenErrorView);
         }
 
         if self.state.settings.show_compression_results {
@@ -1,30 +1,94 @@
+use crate::services::file_name_template::DEFAULT_TEMPLATE;
+use crate::services::image_service::{ExistingFilePolicy, LargerOutputPolicy, OutputFormat};
 use crate::services::theme_service::string_to_theme;
 use etcetera::{AppStrategy, AppStrategyArgs, choose_app_strategy};
 use iced::Theme;
 use log::{error, info};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::OnceLock;
 
 static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+static BACKUP_ROOT: OnceLock<PathBuf> = OnceLock::new();
+static JOURNAL_PATH: OnceLock<PathBuf> = OnceLock::new();
 static CONFIG_TMP_PATH: OnceLock<PathBuf> = OnceLock::new();
 
+/// Returns the OS-specific directory strategy for the application.
+///
+/// # Returns
+///
+/// The app strategy used to locate the config and data directories.
+fn app_strategy() -> impl AppStrategy {
+    choose_app_strategy(AppStrategyArgs {
+        top_level_domain: "com".to_string(),
+        author: "CodeDead".to_string(),
+        app_name: "Compressr".to_string(),
+    })
+    .expect("Failed to determine OS config directory")
+}
+
 /// Returns a reference to the configuration file's path as a static `PathBuf`.
 ///
 /// # Returns
 ///
 /// A reference to the configuration file's path.
 fn config_path() -> &'static PathBuf {
-    CONFIG_PATH.get_or_init(|| {
-        let strategy = choose_app_strategy(AppStrategyArgs {
-            top_level_domain: "com".to_string(),
-            author: "CodeDead".to_string(),
-            app_name: "Compressr".to_string(),
-        })
-        .expect("Failed to determine OS config directory");
-        strategy.in_config_dir("config.json")
-    })
+    CONFIG_PATH.get_or_init(|| app_strategy().in_config_dir("config.json"))
+}
+
+/// Returns the directory under which timestamped in-place compression backups are kept.
+///
+/// # Returns
+///
+/// A reference to the backup root in the OS data directory.
+pub fn backup_root() -> &'static PathBuf {
+    BACKUP_ROOT.get_or_init(|| app_strategy().in_data_dir("backups"))
+}
+
+/// Returns the path of the journal that records the progress of the running batch.
+///
+/// # Returns
+///
+/// A reference to the journal file's path in the OS config directory.
+pub fn journal_path() -> &'static PathBuf {
+    JOURNAL_PATH.get_or_init(|| app_strategy().in_config_dir("batch-journal.jsonl"))
+}
+
+/// Where in-place compression keeps the originals it replaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupLocation {
+    /// A folder per batch, named after its start time, below [`backup_root`].
+    Timestamped,
+    /// A `.compressr-backup` folder next to each original.
+    Sibling,
+}
+
+impl BackupLocation {
+    pub const ALL: [BackupLocation; 2] = [Self::Timestamped, Self::Sibling];
+}
+
+/// How originals are removed after compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeleteMode {
+    /// Move originals to the system trash so they can be restored.
+    Trash,
+    /// Delete originals permanently.
+    Permanent,
+}
+
+impl DeleteMode {
+    pub const ALL: [DeleteMode; 2] = [Self::Trash, Self::Permanent];
+}
+
+/// Returns the number of files compressed at once by default: one per available CPU.
+///
+/// # Returns
+///
+/// The available parallelism, or `1` if it cannot be determined.
+pub fn default_concurrent_jobs() -> u32 {
+    std::thread::available_parallelism().map_or(1, |n| n.get() as u32)
 }
 
 /// Returns a reference to the temporary config file path as a static `PathBuf`.
@@ -55,6 +119,7 @@ fn deserialize_theme<'de, D: Deserializer<'de>>(d: D) -> Result<Theme, D::Error>
 }
 
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
     pub auto_update: bool,
     pub update_server: String,
@@ -64,10 +129,27 @@ pub struct Settings {
     )]
     pub theme: Theme,
     pub delete_files_after_compression: bool,
+    pub delete_mode: DeleteMode,
+    pub in_place: bool,
+    pub backup_location: BackupLocation,
     pub language_key: String,
     pub preserve_exif: bool,
     pub show_compression_results: bool,
     pub recursive_folder_scan: bool,
+    pub preserve_folder_structure: bool,
+    pub avif_speed: u8,
+    pub png_optimization_level: u8,
+    pub png_zopfli: bool,
+    pub lossy_png: bool,
+    pub max_frame_rate: Option<u32>,
+    pub optimize_gif_palette: bool,
+    pub downscale_to_target_size: bool,
+    pub larger_output_policy: LargerOutputPolicy,
+    pub existing_file_policy: ExistingFilePolicy,
+    pub format_mapping: BTreeMap<OutputFormat, OutputFormat>,
+    pub file_name_template: String,
+    pub max_concurrent_jobs: u32,
+    pub pixel_budget_megapixels: Option<u32>,
 }
 
 impl Default for Settings {
@@ -84,10 +166,27 @@ impl Default for Settings {
                     .to_string(),
             theme: Theme::Oxocarbon,
             delete_files_after_compression: false,
+            delete_mode: DeleteMode::Trash,
+            in_place: false,
+            backup_location: BackupLocation::Timestamped,
             language_key: "en_US".to_string(),
             preserve_exif: false,
             show_compression_results: true,
             recursive_folder_scan: false,
+            preserve_folder_structure: false,
+            avif_speed: 6,
+            png_optimization_level: 2,
+            png_zopfli: false,
+            lossy_png: false,
+            max_frame_rate: None,
+            optimize_gif_palette: false,
+            downscale_to_target_size: false,
+            larger_output_policy: LargerOutputPolicy::WriteAnyway,
+            existing_file_policy: ExistingFilePolicy::Ask,
+            format_mapping: OutputFormat::default_mapping(),
+            file_name_template: DEFAULT_TEMPLATE.to_string(),
+            max_concurrent_jobs: default_concurrent_jobs(),
+            pixel_budget_megapixels: None,
         }
     }
 }
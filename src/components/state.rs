@@ -1,9 +1,15 @@
 use crate::components::settings::Settings;
 use crate::models::language::Language;
-use crate::services::image_service::{CompressionResult, OutputFormat};
+use crate::services::batch_journal::{BatchJournal, BatchSettings};
+use crate::services::compression_queue::{CompressionQueue, ResolvedBatch};
+use crate::services::image_service::{
+    CompressionParams, CompressionResult, JpegOptions, OutputFormat, SourceInfo,
+};
 use iced::widget::image;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
 
 pub struct MainViewIcons {
     pub settings: image::Handle,
@@ -14,12 +20,20 @@ pub struct MainViewIcons {
 
 pub struct State {
     pub input_path: Vec<String>,
+    pub input_root: Option<PathBuf>,
+    pub file_name_preview: Option<String>,
+    pub file_name_preview_source: Option<(String, SourceInfo)>,
+    pub existing_files_prompt: Option<usize>,
+    pub pending_batch: Option<ResolvedBatch>,
     pub output_path: String,
     pub scale: u32,
     pub height: Option<u32>,
     pub width: Option<u32>,
     pub quality: u8,
+    pub target_size_kib: Option<u32>,
+    pub min_similarity: Option<f32>,
     pub format: OutputFormat,
+    pub jpeg_options: JpegOptions,
     pub is_compressing: bool,
     pub compression_results: Vec<CompressionResult>,
     pub last_error_message: Option<String>,
@@ -30,9 +44,19 @@ pub struct State {
     pub languages: Vec<Language>,
     pub main_view_icons: MainViewIcons,
     pub show_input_dropdown: bool,
+    pub show_jpeg_options_dropdown: bool,
     pub progress_completed: usize,
     pub progress_total: usize,
+    pub progress_started: Option<(Instant, usize)>,
+    pub progress_paused: Duration,
+    pub paused_since: Option<Instant>,
     pub compression_aborted: Arc<AtomicBool>,
+    pub compression_queue: CompressionQueue,
+    pub batch_params: Option<CompressionParams>,
+    pub batch_settings: BatchSettings,
+    pub is_cancelling: bool,
+    pub cancelled_summary: Option<(usize, usize)>,
+    pub interrupted_batch: Option<BatchJournal>,
 }
 
 impl Default for State {
@@ -95,12 +119,20 @@ impl State {
 
         State {
             input_path: Vec::new(),
+            input_root: None,
+            file_name_preview: None,
+            file_name_preview_source: None,
+            existing_files_prompt: None,
+            pending_batch: None,
             output_path: String::new(),
             scale: 100,
             height: None,
             width: None,
             quality: 100,
+            target_size_kib: None,
+            min_similarity: None,
             format: OutputFormat::Jpeg,
+            jpeg_options: JpegOptions::default(),
             is_compressing: false,
             compression_results: Vec::new(),
             last_error_message: None,
@@ -111,12 +143,39 @@ impl State {
             languages,
             main_view_icons,
             show_input_dropdown: false,
+            show_jpeg_options_dropdown: false,
             progress_completed: 0,
             progress_total: 0,
+            progress_started: None,
+            progress_paused: Duration::ZERO,
+            paused_since: None,
             compression_aborted: Arc::new(AtomicBool::new(false)),
+            compression_queue: CompressionQueue::default(),
+            batch_params: None,
+            batch_settings: BatchSettings::default(),
+            is_cancelling: false,
+            cancelled_summary: None,
+            interrupted_batch: None,
         }
     }
 
+    /// Returns how long the current batch has been compressing, leaving out the time it spent
+    /// paused.
+    ///
+    /// # Returns
+    ///
+    /// The active time along with the number of files finished before it started counting, or
+    /// `None` if no batch is running.
+    pub fn progress_elapsed(&self) -> Option<(Duration, usize)> {
+        self.progress_started.map(|(started, completed_before)| {
+            let paused = self.progress_paused
+                + self
+                    .paused_since
+                    .map_or(Duration::ZERO, |since| since.elapsed());
+            (started.elapsed().saturating_sub(paused), completed_before)
+        })
+    }
+
     /// Returns a reference to the language matching the current `language_key` setting,
     /// falling back to the first language if no match is found.
     ///
@@ -134,3 +193,26 @@ impl State {
             )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_elapsed_leaves_out_paused_time() {
+        let mut state = State::default();
+        assert_eq!(state.progress_elapsed(), None);
+
+        let now = Instant::now();
+        state.progress_started = Some((now - Duration::from_secs(100), 3));
+        state.progress_paused = Duration::from_secs(30);
+        let (elapsed, completed_before) = state.progress_elapsed().unwrap();
+        assert_eq!(completed_before, 3);
+        assert!(elapsed >= Duration::from_secs(70) && elapsed < Duration::from_secs(71));
+
+        // A pause that is still going on counts as well.
+        state.paused_since = Some(now - Duration::from_secs(20));
+        let (elapsed, _) = state.progress_elapsed().unwrap();
+        assert!(elapsed >= Duration::from_secs(50) && elapsed < Duration::from_secs(51));
+    }
+}
@@ -38,13 +38,13 @@ impl WindowKind {
     /// The default window size in pixels as a tuple `(width, height)`.
     pub fn default_size(self) -> (f32, f32) {
         match self {
-            WindowKind::Main => (650.0, 385.0),
-            WindowKind::Settings => (500.0, 400.0),
+            WindowKind::Main => (650.0, 430.0),
+            WindowKind::Settings => (500.0, 620.0),
             WindowKind::Update => (400.0, 190.0),
             WindowKind::Error => (400.0, 210.0),
             WindowKind::About => (450.0, 270.0),
             WindowKind::NoUpdate => (400.0, 180.0),
-            WindowKind::Results => (600.0, 400.0),
+            WindowKind::Results => (760.0, 400.0),
         }
     }
 
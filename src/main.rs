@@ -1,5 +1,6 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod cli;
 mod components;
 mod models;
 mod services;
@@ -10,18 +11,50 @@ use log::info;
 
 /// The main entry point of the application, initializing the logger and running the Iced application with the specified configuration.
 ///
+/// When the command line contains one of the CLI options, the images are compressed headlessly
+/// instead and the process exits with the status reported by [`cli::run`]. Other arguments are
+/// treated as image files to load into the user interface.
+///
 /// # Returns
 ///
 /// An Iced Result indicating the success or failure of running the application.
 fn main() -> iced::Result {
     env_logger::init();
+    // Lets `image` decode JPEG XL, which it does not support on its own.
+    jxl_oxide::integration::register_image_decoding_hook();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::is_cli_invocation(&args) {
+        #[cfg(windows)]
+        attach_parent_console();
+        std::process::exit(cli::run(args));
+    }
 
     info!("Starting Compressr");
 
-    iced::daemon(App::new, App::update, App::view)
+    iced::daemon(move || App::new(args.clone()), App::update, App::view)
         .subscription(App::subscription)
         .title(App::title)
         .theme(App::theme)
         .scale_factor(App::scale_factor)
         .run()
 }
+
+/// Attaches to the console of the process that started the application, if any.
+///
+/// Release builds use the Windows GUI subsystem, so without a console the output of the
+/// command line mode would be lost.
+#[cfg(windows)]
+fn attach_parent_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+
+    // Without a parent console (e.g. when started from Explorer) there is nothing to attach to.
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
@@ -1,2 +1,3 @@
+pub(crate) mod labeled_option;
 pub(crate) mod language;
 pub(crate) mod version;
@@ -0,0 +1,44 @@
+/// A value paired with a localized label, for use in pick lists.
+///
+/// `pick_list` renders its options through [`Display`](std::fmt::Display), so wrapping a
+/// value together with its translated name lets settings enums be shown in the active
+/// language without implementing `Display` per language.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabeledOption<T> {
+    pub value: T,
+    pub label: String,
+}
+
+impl<T> LabeledOption<T> {
+    /// Initialize a new LabeledOption.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value represented by the option.
+    /// * `label` - The localized label to display for the option.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `LabeledOption`.
+    pub fn new(value: T, label: &str) -> Self {
+        Self {
+            value,
+            label: label.to_string(),
+        }
+    }
+}
+
+impl<T> std::fmt::Display for LabeledOption<T> {
+    /// Formats the option as its localized label.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - A mutable reference to a `Formatter` where the label will be written.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating whether the formatting was successful or if an error occurred.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.label)
+    }
+}
@@ -10,10 +10,36 @@ pub struct Language {
     pub output: String,
     pub browse: String,
     pub format: String,
+    #[serde(rename = "jpegOptions")]
+    pub jpeg_options: String,
+    #[serde(rename = "progressiveJpeg")]
+    pub progressive_jpeg: String,
+    #[serde(rename = "optimizeHuffman")]
+    pub optimize_huffman: String,
+    #[serde(rename = "trellisQuantization")]
+    pub trellis_quantization: String,
+    #[serde(rename = "losslessJpeg")]
+    pub lossless_jpeg: String,
+    #[serde(rename = "jpegXlLossless")]
+    pub jpeg_xl_lossless: String,
+    #[serde(rename = "chromaSubsampling")]
+    pub chroma_subsampling: String,
     pub quality: String,
     pub scale: String,
     pub width: String,
     pub height: String,
+    #[serde(rename = "targetSize")]
+    pub target_size: String,
+    #[serde(rename = "minSimilarity")]
+    pub min_similarity: String,
+    #[serde(rename = "outputNamePreview")]
+    pub output_name_preview: String,
+    #[serde(rename = "existingFilesPrompt")]
+    pub existing_files_prompt: String,
+    #[serde(rename = "interruptedBatchPrompt")]
+    pub interrupted_batch_prompt: String,
+    pub discard: String,
+    pub cancel: String,
     pub compress: String,
     #[serde(rename = "latestVersionInstalled")]
     pub latest_version_installed: String,
@@ -37,12 +63,64 @@ pub struct Language {
     pub automatically_check_for_updates: String,
     #[serde(rename = "deleteOriginalFilesAfterCompression")]
     pub delete_original_files_after_compression: String,
+    #[serde(rename = "deleteMode")]
+    pub delete_mode: String,
+    #[serde(rename = "moveToTrash")]
+    pub move_to_trash: String,
+    #[serde(rename = "deletePermanently")]
+    pub delete_permanently: String,
+    #[serde(rename = "inPlace")]
+    pub in_place: String,
+    #[serde(rename = "backupLocation")]
+    pub backup_location: String,
+    #[serde(rename = "backupTimestamped")]
+    pub backup_timestamped: String,
+    #[serde(rename = "backupSibling")]
+    pub backup_sibling: String,
     #[serde(rename = "preserveExifData")]
     pub preserve_exif_data: String,
     #[serde(rename = "showCompressionResults")]
     pub show_compression_results: String,
     #[serde(rename = "recursiveFolderScan")]
     pub recursive_folder_scan: String,
+    #[serde(rename = "preserveFolderStructure")]
+    pub preserve_folder_structure: String,
+    #[serde(rename = "avifSpeed")]
+    pub avif_speed: String,
+    #[serde(rename = "pngOptimizationLevel")]
+    pub png_optimization_level: String,
+    #[serde(rename = "pngZopfli")]
+    pub png_zopfli: String,
+    #[serde(rename = "lossyPng")]
+    pub lossy_png: String,
+    #[serde(rename = "optimizeGifPalette")]
+    pub optimize_gif_palette: String,
+    #[serde(rename = "maxFrameRate")]
+    pub max_frame_rate: String,
+    #[serde(rename = "maxConcurrentJobs")]
+    pub max_concurrent_jobs: String,
+    #[serde(rename = "pixelBudget")]
+    pub pixel_budget: String,
+    #[serde(rename = "downscaleToTargetSize")]
+    pub downscale_to_target_size: String,
+    #[serde(rename = "largerOutput")]
+    pub larger_output: String,
+    #[serde(rename = "writeAnyway")]
+    pub write_anyway: String,
+    #[serde(rename = "keepOriginal")]
+    pub keep_original: String,
+    pub skip: String,
+    #[serde(rename = "existingFiles")]
+    pub existing_files: String,
+    pub overwrite: String,
+    pub rename: String,
+    pub ask: String,
+    #[serde(rename = "formatMapping")]
+    pub format_mapping: String,
+    #[serde(rename = "fileNameTemplate")]
+    pub file_name_template: String,
+    #[serde(rename = "availableTokens")]
+    pub available_tokens: String,
     pub theme: String,
     #[serde(rename = "selectTheme")]
     pub select_theme: String,
@@ -70,8 +148,28 @@ pub struct Language {
     #[serde(rename = "compressedSize")]
     pub compressed_size: String,
     pub saved: String,
+    #[serde(rename = "usedQuality")]
+    pub used_quality: String,
+    pub similarity: String,
+    pub duration: String,
+    #[serde(rename = "phaseTimings")]
+    pub phase_timings: String,
+    #[serde(rename = "originalKept")]
+    pub original_kept: String,
+    pub skipped: String,
+    #[serde(rename = "alreadyExists")]
+    pub already_exists: String,
     #[serde(rename = "filesTotal")]
     pub files_total: String,
     #[serde(rename = "compressingProgress")]
     pub compressing_progress: String,
+    #[serde(rename = "pausedProgress")]
+    pub paused_progress: String,
+    #[serde(rename = "batchStatistics")]
+    pub batch_statistics: String,
+    pub pause: String,
+    pub resume: String,
+    pub cancelling: String,
+    #[serde(rename = "compressionCancelled")]
+    pub compression_cancelled: String,
 }
@@ -2,8 +2,15 @@ use log::error;
 use reqwest::Url;
 use std::process::Command;
 
+pub(crate) mod animation;
+pub(crate) mod batch_journal;
+pub(crate) mod compression_queue;
+pub(crate) mod file_name_template;
 pub(crate) mod folder_scanner;
 pub(crate) mod image_service;
+pub(crate) mod jpeg_lossless;
+pub(crate) mod jpeg_xl;
+pub(crate) mod similarity;
 pub(crate) mod theme_service;
 pub(crate) mod update_service;
 
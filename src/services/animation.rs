@@ -0,0 +1,433 @@
+use image::codecs::gif::GifDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::metadata::LoopCount;
+use image::{AnimationDecoder, ImageFormat, RgbaImage};
+use libwebp_sys as webp_ffi;
+use std::io::Cursor;
+use std::os::raw::c_int;
+use std::ptr;
+use std::time::Duration;
+
+/// The NeuQuant sampling speed (1–30) used when GIF palettes are not optimized.
+const GIF_QUANTIZER_SPEED: i32 = 10;
+
+/// A single frame of an animation.
+#[derive(Debug, Clone)]
+pub struct AnimationFrame {
+    /// The composited frame, covering the whole canvas.
+    pub image: RgbaImage,
+    /// How long the frame is shown.
+    pub delay: Duration,
+}
+
+/// The frames of an animated GIF or WebP, with their timing.
+#[derive(Clone)]
+pub struct Animation {
+    /// The frames in display order. Decoding yields at least two, and
+    /// [`Animation::limit_frame_rate`] may merge them down to one but never drops them all.
+    pub frames: Vec<AnimationFrame>,
+    /// How many times the animation plays.
+    pub loop_count: LoopCount,
+}
+
+impl Animation {
+    /// Decodes every frame of an animated GIF or WebP.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw` - The source file bytes.
+    ///
+    /// # Returns
+    ///
+    /// The animation, `Ok(None)` if the source is not an animated GIF or WebP, or an error
+    /// message if its frames cannot be decoded.
+    pub fn decode(raw: &[u8]) -> Result<Option<Self>, String> {
+        let (loop_count, frames) = match image::guess_format(raw) {
+            Ok(ImageFormat::Gif) => {
+                let decoder = GifDecoder::new(Cursor::new(raw))
+                    .map_err(|e| format!("Failed to read GIF: {e}"))?;
+                (decoder.loop_count(), decoder.into_frames())
+            }
+            Ok(ImageFormat::WebP) => {
+                let decoder = WebPDecoder::new(Cursor::new(raw))
+                    .map_err(|e| format!("Failed to read WebP: {e}"))?;
+                if !decoder.has_animation() {
+                    return Ok(None);
+                }
+                (decoder.loop_count(), decoder.into_frames())
+            }
+            _ => return Ok(None),
+        };
+
+        let frames = frames
+            .map(|frame| {
+                frame.map(|frame| AnimationFrame {
+                    delay: frame.delay().into(),
+                    image: frame.into_buffer(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to decode animation frames: {e}"))?;
+
+        Ok((frames.len() > 1).then_some(Self { frames, loop_count }))
+    }
+
+    /// Returns the canvas dimensions of the animation.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of the width and height in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.frames[0].image.dimensions()
+    }
+
+    /// Drops frames so the animation plays at no more than `max_fps` frames per second.
+    ///
+    /// A frame shown for less than the frame interval absorbs the frames after it, along with
+    /// their delays, so the total duration is kept. Frames without a delay are left alone.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_fps` - The maximum frame rate. `0` leaves the animation unchanged.
+    pub fn limit_frame_rate(&mut self, max_fps: u32) {
+        if max_fps == 0 {
+            return;
+        }
+
+        let interval = Duration::from_secs(1) / max_fps;
+        let mut kept: Vec<AnimationFrame> = Vec::with_capacity(self.frames.len());
+        for frame in self.frames.drain(..) {
+            match kept.last_mut() {
+                Some(last) if !last.delay.is_zero() && last.delay < interval => {
+                    last.delay += frame.delay;
+                }
+                _ => kept.push(frame),
+            }
+        }
+        self.frames = kept;
+    }
+
+    /// Encodes the animation as an animated GIF.
+    ///
+    /// GIF only supports 256 colors and fully transparent pixels. By default every frame gets
+    /// its own NeuQuant palette; an optimized palette is instead computed once with imagequant
+    /// from all frames and shared by them, which gives better colors and smaller files.
+    ///
+    /// # Arguments
+    ///
+    /// * `optimize_palette` - Whether to compute a shared, optimized palette.
+    ///
+    /// # Returns
+    ///
+    /// The GIF bytes, or an error message on failure.
+    pub fn encode_gif(&self, optimize_palette: bool) -> Result<Vec<u8>, String> {
+        let (width, height) = self.dimensions();
+        let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+            return Err(format!("{width}x{height} is too large for a GIF"));
+        };
+
+        let (global_palette, mut frames) = if optimize_palette {
+            self.quantize_gif_frames(width, height)?
+        } else {
+            let frames = self
+                .frames
+                .iter()
+                .map(|frame| {
+                    let mut pixels = frame.image.as_raw().clone();
+                    gif::Frame::from_rgba_speed(width, height, &mut pixels, GIF_QUANTIZER_SPEED)
+                })
+                .collect();
+            (Vec::new(), frames)
+        };
+
+        let mut encoder = gif::Encoder::new(Vec::new(), width, height, &global_palette)
+            .map_err(|e| format!("Failed to encode GIF: {e}"))?;
+        encoder
+            .set_repeat(match self.loop_count {
+                LoopCount::Infinite => gif::Repeat::Infinite,
+                LoopCount::Finite(n) => {
+                    gif::Repeat::Finite(u16::try_from(n.get()).unwrap_or(u16::MAX))
+                }
+            })
+            .map_err(|e| format!("Failed to encode GIF: {e}"))?;
+        for (frame, source) in frames.iter_mut().zip(&self.frames) {
+            // GIF delays are in hundredths of a second.
+            frame.delay = u16::try_from(source.delay.as_millis() / 10).unwrap_or(u16::MAX);
+            // Every frame covers the whole canvas, so transparent pixels must not show the
+            // previous frame.
+            frame.dispose = gif::DisposalMethod::Background;
+            encoder
+                .write_frame(frame)
+                .map_err(|e| format!("Failed to encode GIF: {e}"))?;
+        }
+        encoder
+            .into_inner()
+            .map_err(|e| format!("Failed to encode GIF: {e}"))
+    }
+
+    /// Maps every frame onto one palette computed with imagequant from all frames, without
+    /// dithering.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The canvas width in pixels.
+    /// * `height` - The canvas height in pixels.
+    ///
+    /// # Returns
+    ///
+    /// The palette as RGB triplets and the indexed frames, or an error message on failure.
+    fn quantize_gif_frames(
+        &self,
+        width: u16,
+        height: u16,
+    ) -> Result<(Vec<u8>, Vec<gif::Frame<'static>>), String> {
+        let pixels: Vec<Vec<imagequant::RGBA>> = self
+            .frames
+            .iter()
+            .map(|frame| {
+                frame
+                    .image
+                    .pixels()
+                    .map(|p| imagequant::RGBA::new(p[0], p[1], p[2], p[3]))
+                    .collect()
+            })
+            .collect();
+
+        let liq = imagequant::new();
+        let mut histogram = imagequant::Histogram::new(&liq);
+        for frame in &pixels {
+            let mut image = liq
+                .new_image_borrowed(frame, width.into(), height.into(), 0.0)
+                .map_err(|e| format!("Failed to prepare GIF quantization: {e}"))?;
+            histogram
+                .add_image(&liq, &mut image)
+                .map_err(|e| format!("Failed to prepare GIF quantization: {e}"))?;
+        }
+        let mut quantized = histogram
+            .quantize(&liq)
+            .map_err(|e| format!("Failed to quantize GIF: {e}"))?;
+        // Dithering refines the palette for every remapped image, which would leave each frame
+        // indexing a slightly different palette. Without it, every frame maps onto this one.
+        quantized
+            .set_dithering_level(0.0)
+            .map_err(|e| format!("Failed to quantize GIF: {e}"))?;
+        let palette = quantized.palette_vec();
+        // GIF has no partial transparency; only the fully transparent color stays clear.
+        let transparent = palette.iter().position(|c| c.a == 0).map(|i| i as u8);
+
+        let mut frames = Vec::with_capacity(pixels.len());
+        for frame in &pixels {
+            let mut image = liq
+                .new_image_borrowed(frame, width.into(), height.into(), 0.0)
+                .map_err(|e| format!("Failed to prepare GIF quantization: {e}"))?;
+            let (_, indexes) = quantized
+                .remapped(&mut image)
+                .map_err(|e| format!("Failed to quantize GIF: {e}"))?;
+            frames.push(gif::Frame::from_indexed_pixels(
+                width,
+                height,
+                indexes,
+                transparent,
+            ));
+        }
+
+        let palette = palette.iter().flat_map(|c| [c.r, c.g, c.b]).collect();
+        Ok((palette, frames))
+    }
+
+    /// Encodes the animation as an animated WebP.
+    ///
+    /// libwebp is used directly because the `webp` crate cannot set the duration of the last
+    /// frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `quality` - The quality (0–100); `100` encodes losslessly.
+    ///
+    /// # Returns
+    ///
+    /// The WebP bytes, or an error message on failure.
+    pub fn encode_webp(&self, quality: u8) -> Result<Vec<u8>, String> {
+        /// Deletes the libwebp animation encoder when dropped.
+        struct EncoderGuard(*mut webp_ffi::WebPAnimEncoder);
+
+        impl Drop for EncoderGuard {
+            fn drop(&mut self) {
+                unsafe { webp_ffi::WebPAnimEncoderDelete(self.0) }
+            }
+        }
+
+        let (width, height) = self.dimensions();
+        let mut config = webp_ffi::WebPConfig::new()
+            .map_err(|_| "Failed to configure WebP encoder".to_string())?;
+        if quality == 100 {
+            config.lossless = 1;
+        } else {
+            config.quality = f32::from(quality);
+        }
+
+        unsafe {
+            let mut options = std::mem::zeroed::<webp_ffi::WebPAnimEncoderOptions>();
+            if webp_ffi::WebPAnimEncoderOptionsInitInternal(
+                &mut options,
+                webp_ffi::WebPGetMuxABIVersion(),
+            ) == 0
+            {
+                return Err("Failed to configure WebP encoder".to_string());
+            }
+            options.anim_params.loop_count = match self.loop_count {
+                LoopCount::Infinite => 0,
+                LoopCount::Finite(n) => c_int::try_from(n.get()).unwrap_or(c_int::MAX),
+            };
+
+            let encoder = EncoderGuard(webp_ffi::WebPAnimEncoderNewInternal(
+                width as c_int,
+                height as c_int,
+                &options,
+                webp_ffi::WebPGetMuxABIVersion(),
+            ));
+            if encoder.0.is_null() {
+                return Err("Failed to create WebP encoder".to_string());
+            }
+            let error = || {
+                let message = webp_ffi::WebPAnimEncoderGetError(encoder.0);
+                if message.is_null() {
+                    "Failed to encode animated WebP".to_string()
+                } else {
+                    format!(
+                        "Failed to encode animated WebP: {}",
+                        std::ffi::CStr::from_ptr(message).to_string_lossy()
+                    )
+                }
+            };
+
+            let mut timestamp: c_int = 0;
+            for frame in &self.frames {
+                let mut picture = webp_ffi::WebPPicture::new()
+                    .map_err(|_| "Failed to create WebP picture".to_string())?;
+                picture.use_argb = 1;
+                picture.width = width as c_int;
+                picture.height = height as c_int;
+                let imported = webp_ffi::WebPPictureImportRGBA(
+                    &mut picture,
+                    frame.image.as_ptr(),
+                    width as c_int * 4,
+                );
+                let added = imported != 0
+                    && webp_ffi::WebPAnimEncoderAdd(encoder.0, &mut picture, timestamp, &config)
+                        != 0;
+                webp_ffi::WebPPictureFree(&mut picture);
+                if !added {
+                    return Err(error());
+                }
+                let delay = c_int::try_from(frame.delay.as_millis()).unwrap_or(c_int::MAX);
+                timestamp = timestamp.saturating_add(delay);
+            }
+            // The final timestamp sets how long the last frame is shown.
+            if webp_ffi::WebPAnimEncoderAdd(encoder.0, ptr::null_mut(), timestamp, ptr::null()) == 0
+            {
+                return Err(error());
+            }
+
+            let mut data = std::mem::zeroed::<webp_ffi::WebPData>();
+            if webp_ffi::WebPAnimEncoderAssemble(encoder.0, &mut data) == 0 {
+                return Err(error());
+            }
+            let bytes = std::slice::from_raw_parts(data.bytes, data.size).to_vec();
+            webp_ffi::WebPDataClear(&mut data);
+            Ok(bytes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a 2x1 animation with one frame per delay, in milliseconds.
+    fn animation(delays: &[u64]) -> Animation {
+        Animation {
+            frames: delays
+                .iter()
+                .enumerate()
+                .map(|(i, &delay)| AnimationFrame {
+                    image: RgbaImage::from_pixel(2, 1, image::Rgba([i as u8, 0, 0, 255])),
+                    delay: Duration::from_millis(delay),
+                })
+                .collect(),
+            loop_count: LoopCount::Infinite,
+        }
+    }
+
+    fn delays(animation: &Animation) -> Vec<u64> {
+        animation
+            .frames
+            .iter()
+            .map(|frame| frame.delay.as_millis() as u64)
+            .collect()
+    }
+
+    fn total(animation: &Animation) -> Duration {
+        animation.frames.iter().map(|frame| frame.delay).sum()
+    }
+
+    #[test]
+    fn zero_max_fps_keeps_every_frame() {
+        let mut animation = animation(&[10, 10, 10]);
+        animation.limit_frame_rate(0);
+        assert_eq!(delays(&animation), [10, 10, 10]);
+    }
+
+    #[test]
+    fn slow_frames_are_kept() {
+        let mut animation = animation(&[200, 300, 250]);
+        animation.limit_frame_rate(5);
+        assert_eq!(delays(&animation), [200, 300, 250]);
+    }
+
+    #[test]
+    fn fast_frames_merge_and_keep_the_duration() {
+        let mut merged = animation(&[100, 100, 100]);
+        merged.limit_frame_rate(5);
+        assert_eq!(delays(&merged), [200, 100]);
+        // The merged frame shows the first of the frames it absorbed.
+        assert_eq!(merged.frames[0].image.get_pixel(0, 0)[0], 0);
+        assert_eq!(merged.frames[1].image.get_pixel(0, 0)[0], 2);
+
+        let mut longer = animation(&[40, 40, 40, 40, 40, 40, 70]);
+        let before = total(&longer);
+        longer.limit_frame_rate(10);
+        assert_eq!(delays(&longer), [120, 120, 70]);
+        assert_eq!(total(&longer), before);
+    }
+
+    #[test]
+    fn zero_delay_frames_are_left_alone() {
+        let mut animation = animation(&[0, 0, 50, 0]);
+        animation.limit_frame_rate(5);
+        assert_eq!(delays(&animation), [0, 0, 50]);
+        assert_eq!(animation.frames.len(), 3);
+    }
+
+    #[test]
+    fn merging_keeps_at_least_one_frame() {
+        let mut animation = animation(&[10, 10]);
+        animation.limit_frame_rate(1);
+        assert_eq!(delays(&animation), [20]);
+        assert_eq!(animation.dimensions(), (2, 1));
+    }
+
+    #[test]
+    fn decoding_requires_two_frames() {
+        let single = animation(&[100]);
+        let two = animation(&[100, 100]);
+        for (source, expected) in [(single, None), (two, Some(2))] {
+            let gif = source.encode_gif(false).unwrap();
+            let decoded = Animation::decode(&gif).unwrap();
+            assert_eq!(decoded.as_ref().map(|a| a.frames.len()), expected);
+            if let Some(decoded) = decoded {
+                assert_eq!(decoded.dimensions(), (2, 1));
+            }
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use crate::services::compression_queue::CompressionJob;
+use crate::services::image_service::CompressionParams;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// A file planned as part of a journaled batch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// The input file path.
+    pub input: String,
+    /// The output path resolved for the file when the batch started.
+    pub output_path: String,
+}
+
+/// The settings that decide what happens to the originals once a batch has finished.
+///
+/// They are journaled with the batch, so a resumed batch ends the way it was started even if
+/// the settings changed in the meantime.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct BatchSettings {
+    /// Whether the originals of written outputs are removed once the batch has finished.
+    pub delete_files_after_compression: bool,
+    /// Whether the batch replaces its sources in place, keeping backups instead.
+    pub in_place: bool,
+}
+
+impl BatchSettings {
+    /// Checks whether originals are removed once the batch has finished.
+    ///
+    /// # Returns
+    ///
+    /// `true` if deleting originals is enabled and the batch does not compress in place, which
+    /// already replaced the originals.
+    pub fn deletes_originals(&self) -> bool {
+        self.delete_files_after_compression && !self.in_place
+    }
+}
+
+/// The plan of a batch, written as the first line of the journal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BatchPlan {
+    params: CompressionParams,
+    #[serde(default)]
+    settings: BatchSettings,
+    files: Vec<JournalEntry>,
+}
+
+/// A batch plan on disk together with the files that have finished, so an interrupted batch
+/// can be resumed.
+///
+/// The journal is a JSON lines file: the first line holds the plan, and each following line
+/// holds the index of a finished file, whether it was compressed or failed. Appending a line per
+/// file keeps updates cheap, and a line cut short by a crash is ignored when loading.
+#[derive(Debug, Clone)]
+pub struct BatchJournal {
+    /// The compression parameters of the batch.
+    pub params: CompressionParams,
+    /// The settings that apply once the batch has finished.
+    pub settings: BatchSettings,
+    /// Every file of the batch, in order.
+    pub files: Vec<JournalEntry>,
+    /// The indices of the files in `files` that have finished.
+    pub finished: HashSet<usize>,
+}
+
+impl BatchJournal {
+    /// Writes the plan of a new batch, replacing any previous journal.
+    ///
+    /// The plan is written to a temporary file that is then renamed over the journal, so a crash
+    /// never leaves a partial plan behind.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The journal file.
+    /// * `params` - The compression parameters of the batch.
+    /// * `settings` - The settings that apply once the batch has finished.
+    /// * `jobs` - The jobs of the batch; their ids must be their indices.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the plan is written, or an error message on failure.
+    pub fn create(
+        path: &Path,
+        params: &CompressionParams,
+        settings: BatchSettings,
+        jobs: &[CompressionJob],
+    ) -> Result<(), String> {
+        let plan = BatchPlan {
+            params: params.clone(),
+            settings,
+            files: jobs
+                .iter()
+                .map(|job| JournalEntry {
+                    input: job.input.clone(),
+                    output_path: job.output_path.clone(),
+                })
+                .collect(),
+        };
+        let mut json = serde_json::to_string(&plan)
+            .map_err(|e| format!("Failed to serialize batch journal: {e}"))?;
+        json.push('\n');
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create journal directory: {e}"))?;
+        }
+
+        let mut tmp_path = path.as_os_str().to_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        if let Err(e) = fs::write(&tmp_path, json) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(format!("Failed to write batch journal: {e}"));
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            let _ = fs::remove_file(path);
+        }
+        fs::rename(&tmp_path, path).map_err(|e| {
+            let _ = fs::remove_file(&tmp_path);
+            format!("Failed to write batch journal: {e}")
+        })
+    }
+
+    /// Records that a file of the current batch has finished, so it is not compressed again on
+    /// resume. Failed files are recorded too; only aborted files are left to retry.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The journal file.
+    /// * `id` - The index of the finished file.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the entry is appended, or an error message on failure.
+    pub fn record_finished(path: &Path, id: usize) -> Result<(), String> {
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open batch journal: {e}"))?;
+        writeln!(file, "{id}").map_err(|e| format!("Failed to update batch journal: {e}"))
+    }
+
+    /// Loads the journal of an unfinished batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The journal file.
+    ///
+    /// # Returns
+    ///
+    /// The journal, `Ok(None)` if there is no journal, or an error message if it cannot be read.
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read batch journal: {e}")),
+        };
+
+        // Every line is written with its newline, so a last line without one was cut short and
+        // may hold a partial id.
+        let complete = contents.rfind('\n').map_or("", |end| &contents[..end]);
+        let mut lines = complete.lines();
+        let plan: BatchPlan = serde_json::from_str(lines.next().unwrap_or_default())
+            .map_err(|e| format!("Failed to parse batch journal: {e}"))?;
+        let finished = lines
+            .filter_map(|line| line.trim().parse::<usize>().ok())
+            .filter(|&id| id < plan.files.len())
+            .collect();
+
+        Ok(Some(Self {
+            params: plan.params,
+            settings: plan.settings,
+            files: plan.files,
+            finished,
+        }))
+    }
+
+    /// Removes the journal once its batch has finished or is discarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The journal file.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the journal is gone, or an error message on failure.
+    pub fn remove(path: &Path) -> Result<(), String> {
+        match fs::remove_file(path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Failed to remove batch journal: {e}")),
+        }
+    }
+
+    /// Returns the jobs of the files that have not finished yet.
+    ///
+    /// # Returns
+    ///
+    /// The remaining jobs in batch order, keeping their original ids.
+    pub fn remaining_jobs(&self) -> Vec<CompressionJob> {
+        self.files
+            .iter()
+            .enumerate()
+            .filter(|(id, _)| !self.finished.contains(id))
+            .map(|(id, entry)| CompressionJob {
+                id,
+                input: entry.input.clone(),
+                output_path: entry.output_path.clone(),
+                pixels: 0,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::file_name_template::FileNameTemplate;
+    use crate::services::image_service::{
+        ExistingFilePolicy, JpegOptions, LargerOutputPolicy, OutputFormat,
+    };
+    use std::sync::Arc;
+
+    /// Returns the path of a journal unique to the calling test, removing any leftover.
+    fn journal_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "compressr-journal-{}-{name}.jsonl",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn params() -> CompressionParams {
+        CompressionParams {
+            output_path: Arc::from("out"),
+            is_output_a_directory: true,
+            scale: 100,
+            width: None,
+            height: None,
+            quality: 80,
+            lossy_png: false,
+            avif_speed: 6,
+            png_optimization_level: 2,
+            png_zopfli: false,
+            max_frame_rate: None,
+            optimize_gif_palette: false,
+            target_size: None,
+            downscale_to_target_size: false,
+            min_similarity: None,
+            larger_output_policy: LargerOutputPolicy::WriteAnyway,
+            existing_file_policy: ExistingFilePolicy::Overwrite,
+            format_mapping: Arc::new(OutputFormat::default_mapping()),
+            source_root: None,
+            file_name_template: Arc::new(FileNameTemplate::default()),
+            in_place_backup: None,
+            jpeg_options: JpegOptions::default(),
+            format: OutputFormat::WebP,
+            preserve_exif: false,
+            output_path_override: None,
+        }
+    }
+
+    fn jobs(count: usize) -> Vec<CompressionJob> {
+        (0..count)
+            .map(|id| CompressionJob {
+                id,
+                input: format!("{id}.png"),
+                output_path: format!("out/{id}_compressed.webp"),
+                pixels: 0,
+            })
+            .collect()
+    }
+
+    fn create(path: &Path, count: usize) {
+        let settings = BatchSettings {
+            delete_files_after_compression: true,
+            in_place: false,
+        };
+        BatchJournal::create(path, &params(), settings, &jobs(count)).unwrap();
+    }
+
+    #[test]
+    fn missing_journal_loads_as_none() {
+        let path = journal_path("missing");
+        assert!(BatchJournal::load(&path).unwrap().is_none());
+        BatchJournal::remove(&path).unwrap();
+    }
+
+    #[test]
+    fn round_trips_the_plan_and_finished_files() {
+        let path = journal_path("round-trip");
+        create(&path, 3);
+        BatchJournal::record_finished(&path, 2).unwrap();
+        BatchJournal::record_finished(&path, 0).unwrap();
+
+        let journal = BatchJournal::load(&path).unwrap().unwrap();
+        assert_eq!(journal.params.format, OutputFormat::WebP);
+        assert_eq!(journal.params.quality, 80);
+        assert!(journal.settings.delete_files_after_compression);
+        assert!(!journal.settings.in_place);
+        assert_eq!(journal.files.len(), 3);
+        assert_eq!(journal.files[1].input, "1.png");
+        assert_eq!(journal.files[1].output_path, "out/1_compressed.webp");
+        assert_eq!(journal.finished, HashSet::from([0, 2]));
+
+        BatchJournal::remove(&path).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn creating_replaces_the_previous_journal() {
+        let path = journal_path("replace");
+        create(&path, 3);
+        BatchJournal::record_finished(&path, 1).unwrap();
+        create(&path, 2);
+
+        let journal = BatchJournal::load(&path).unwrap().unwrap();
+        assert_eq!(journal.files.len(), 2);
+        assert!(journal.finished.is_empty());
+
+        BatchJournal::remove(&path).unwrap();
+    }
+
+    #[test]
+    fn ignores_a_truncated_last_line() {
+        let path = journal_path("truncated");
+        create(&path, 20);
+        BatchJournal::record_finished(&path, 3).unwrap();
+        // A crash while appending `12` left only its first digit behind.
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"1")
+            .unwrap();
+
+        let journal = BatchJournal::load(&path).unwrap().unwrap();
+        assert_eq!(journal.finished, HashSet::from([3]));
+
+        BatchJournal::remove(&path).unwrap();
+    }
+
+    #[test]
+    fn truncated_plan_fails_to_load() {
+        let path = journal_path("truncated-plan");
+        create(&path, 2);
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::write(&path, contents.trim_end()).unwrap();
+
+        assert!(BatchJournal::load(&path).is_err());
+
+        BatchJournal::remove(&path).unwrap();
+    }
+
+    #[test]
+    fn ignores_out_of_range_and_invalid_ids() {
+        let path = journal_path("out-of-range");
+        create(&path, 2);
+        for id in [1, 2, 99] {
+            BatchJournal::record_finished(&path, id).unwrap();
+        }
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"x\n-1\n")
+            .unwrap();
+
+        let journal = BatchJournal::load(&path).unwrap().unwrap();
+        assert_eq!(journal.finished, HashSet::from([1]));
+
+        BatchJournal::remove(&path).unwrap();
+    }
+
+    #[test]
+    fn remaining_jobs_keep_their_ids() {
+        let path = journal_path("remaining");
+        create(&path, 4);
+        BatchJournal::record_finished(&path, 0).unwrap();
+        BatchJournal::record_finished(&path, 2).unwrap();
+
+        let remaining = BatchJournal::load(&path).unwrap().unwrap().remaining_jobs();
+        assert_eq!(
+            remaining.iter().map(|job| job.id).collect::<Vec<_>>(),
+            [1, 3]
+        );
+        assert_eq!(remaining[1].input, "3.png");
+        assert_eq!(remaining[1].output_path, "out/3_compressed.webp");
+
+        BatchJournal::remove(&path).unwrap();
+    }
+
+    #[test]
+    fn recording_without_a_journal_fails() {
+        let path = journal_path("no-journal");
+        assert!(BatchJournal::record_finished(&path, 0).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+use crate::services::image_service::{CompressionParams, ExistingFilePolicy, ImageService};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+
+/// A single file waiting to be compressed as part of a batch.
+#[derive(Debug, Clone)]
+pub struct CompressionJob {
+    /// Identifies the job within its batch.
+    pub id: usize,
+    /// The input file path.
+    pub input: String,
+    /// The pre-resolved, batch-unique output path.
+    pub output_path: String,
+    /// The number of source pixels, counted once when the job is queued with a pixel budget.
+    pub pixels: u64,
+}
+
+/// The jobs of a batch whose output paths were resolved, ready to be queued.
+#[derive(Debug, Clone)]
+pub struct ResolvedBatch {
+    /// The jobs in input order.
+    pub jobs: Vec<CompressionJob>,
+    /// The compression parameters shared across the batch.
+    pub params: CompressionParams,
+    /// How many outputs already exist when the existing file policy is
+    /// [`ExistingFilePolicy::Ask`], so the user must choose a policy first; `0` otherwise.
+    pub existing: usize,
+}
+
+/// A bounded queue of compression jobs.
+///
+/// At most `max_jobs` jobs run at once, and none start while the queue is paused. When a pixel
+/// budget is set, a job is only started if the pixels of all running jobs, including the new
+/// one, stay within the budget; a single job larger than the budget still runs on its own so it
+/// cannot stall the batch. With a budget, no job starts until the pixel counts are set through
+/// [`CompressionQueue::set_pixel_counts`], so the files are read off the UI thread.
+#[derive(Debug, Default)]
+pub struct CompressionQueue {
+    pending: VecDeque<CompressionJob>,
+    running: HashMap<usize, u64>,
+    max_jobs: usize,
+    pixel_budget: Option<u64>,
+    running_pixels: u64,
+    paused: bool,
+    counting: bool,
+}
+
+impl CompressionQueue {
+    /// Creates a queue holding the given jobs.
+    ///
+    /// # Arguments
+    ///
+    /// * `jobs` - The jobs to run, in order. Their ids must be unique.
+    /// * `max_jobs` - The maximum number of jobs to run at once. Values below 1 are treated as 1.
+    /// * `pixel_budget` - The maximum total number of source pixels across running jobs, if any.
+    ///
+    /// # Returns
+    ///
+    /// A queue with every job pending. With a pixel budget, the jobs wait for their pixel counts.
+    pub fn new(
+        jobs: impl IntoIterator<Item = CompressionJob>,
+        max_jobs: usize,
+        pixel_budget: Option<u64>,
+    ) -> Self {
+        Self {
+            pending: jobs.into_iter().collect(),
+            running: HashMap::new(),
+            max_jobs: max_jobs.max(1),
+            pixel_budget,
+            running_pixels: 0,
+            paused: false,
+            counting: pixel_budget.is_some(),
+        }
+    }
+
+    /// Takes every job that may start now, marking them as running.
+    ///
+    /// # Returns
+    ///
+    /// The jobs to start, in queue order; none while the queue is paused or counting pixels.
+    pub fn start_ready(&mut self) -> Vec<CompressionJob> {
+        let mut ready = Vec::new();
+        while !self.paused && !self.counting && self.running.len() < self.max_jobs {
+            let Some(job) = self.pending.front() else {
+                break;
+            };
+            let pixels = job.pixels;
+            if let Some(budget) = self.pixel_budget
+                && !self.running.is_empty()
+                && self.running_pixels + pixels > budget
+            {
+                break;
+            }
+
+            let job = self.pending.pop_front().expect("the front job exists");
+            self.running.insert(job.id, pixels);
+            self.running_pixels += pixels;
+            ready.push(job);
+        }
+        ready
+    }
+
+    /// Returns the inputs whose pixels must be counted before any job can start.
+    ///
+    /// # Returns
+    ///
+    /// The inputs of the pending jobs in queue order, or `None` if no counts are needed.
+    pub fn inputs_to_count(&self) -> Option<Vec<String>> {
+        self.counting
+            .then(|| self.pending.iter().map(|job| job.input.clone()).collect())
+    }
+
+    /// Stores the pixel counts of the pending jobs and lets them start.
+    ///
+    /// # Arguments
+    ///
+    /// * `counts` - The pixel counts in the order of [`CompressionQueue::inputs_to_count`]. Jobs
+    ///   without a count, e.g. because the pending jobs were cleared, count as `0` pixels.
+    pub fn set_pixel_counts(&mut self, counts: Vec<u64>) {
+        for (job, pixels) in self.pending.iter_mut().zip(counts) {
+            job.pixels = pixels;
+        }
+        self.counting = false;
+    }
+
+    /// Pauses or resumes starting queued jobs. Running jobs are not affected.
+    ///
+    /// # Arguments
+    ///
+    /// * `paused` - Whether queued jobs should wait.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Checks whether the queue is paused.
+    ///
+    /// # Returns
+    ///
+    /// `true` if queued jobs are waiting for the queue to be resumed, `false` otherwise.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Drops every job that has not started yet, e.g. when the batch is cancelled.
+    pub fn clear_pending(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Checks whether any job is still running.
+    ///
+    /// # Returns
+    ///
+    /// `true` if at least one started job has not finished yet or the pixel counts are still
+    /// being read, `false` otherwise.
+    pub fn has_running(&self) -> bool {
+        !self.running.is_empty() || self.counting
+    }
+
+    /// Marks a running job as finished, freeing its slot and pixels.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the finished job.
+    pub fn finish(&mut self, id: usize) {
+        if let Some(pixels) = self.running.remove(&id) {
+            self.running_pixels -= pixels;
+        }
+    }
+}
+
+/// Reads the pixel count of an image from its header without decoding it.
+///
+/// The format is detected from the contents, so JPEG XL files are read through the registered
+/// decoding hook whatever their extension.
+///
+/// # Arguments
+///
+/// * `path` - The image file.
+///
+/// # Returns
+///
+/// The number of pixels, or `0` if the header cannot be read; the job then fails on its own.
+pub fn source_pixels(path: &str) -> u64 {
+    image::ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok())
+        .map(|(w, h)| u64::from(w) * u64::from(h))
+        .unwrap_or(0)
+}
+
+/// Resolves the output paths of a batch and turns its inputs into jobs.
+///
+/// Resolving may read every input (e.g. for `{hash8}` or `Original` output), so this runs off
+/// the UI thread.
+///
+/// # Arguments
+///
+/// * `service` - The image service resolving the paths.
+/// * `input` - The input file paths, in order.
+/// * `params` - The compression parameters shared across the batch.
+///
+/// # Returns
+///
+/// The batch, with its existing outputs counted when the policy is to ask.
+pub fn resolve_batch(
+    service: &ImageService,
+    input: Vec<String>,
+    params: CompressionParams,
+) -> ResolvedBatch {
+    let output_paths = service.resolve_unique_output_paths(&input, &params);
+    let jobs: Vec<CompressionJob> = input
+        .into_iter()
+        .zip(output_paths)
+        .enumerate()
+        .map(|(id, (input, output_path))| CompressionJob {
+            id,
+            input,
+            output_path,
+            pixels: 0,
+        })
+        .collect();
+
+    // In place, existing files are never replaced, so there is nothing to ask. Replacing a
+    // source itself is expected rather than a conflict.
+    let existing = if params.existing_file_policy == ExistingFilePolicy::Ask
+        && params.in_place_backup.is_none()
+    {
+        jobs.iter()
+            .filter(|job| job.output_path != job.input && Path::new(&job.output_path).exists())
+            .count()
+    } else {
+        0
+    };
+
+    ResolvedBatch {
+        jobs,
+        params,
+        existing,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jobs(count: usize) -> Vec<CompressionJob> {
+        (0..count)
+            .map(|id| CompressionJob {
+                id,
+                input: format!("{id}.png"),
+                output_path: format!("{id}_compressed.png"),
+                pixels: 0,
+            })
+            .collect()
+    }
+
+    fn ids(jobs: &[CompressionJob]) -> Vec<usize> {
+        jobs.iter().map(|job| job.id).collect()
+    }
+
+    #[test]
+    fn starts_at_most_max_jobs() {
+        let mut queue = CompressionQueue::new(jobs(5), 2, None);
+        assert_eq!(queue.inputs_to_count(), None);
+        assert_eq!(ids(&queue.start_ready()), [0, 1]);
+        assert!(queue.start_ready().is_empty());
+
+        queue.finish(1);
+        assert_eq!(ids(&queue.start_ready()), [2]);
+        queue.finish(0);
+        queue.finish(2);
+        assert_eq!(ids(&queue.start_ready()), [3, 4]);
+        assert!(queue.has_running());
+        queue.finish(3);
+        queue.finish(4);
+        assert!(!queue.has_running());
+    }
+
+    #[test]
+    fn zero_max_jobs_runs_one_at_a_time() {
+        let mut queue = CompressionQueue::new(jobs(2), 0, None);
+        assert_eq!(ids(&queue.start_ready()), [0]);
+        assert!(queue.start_ready().is_empty());
+    }
+
+    #[test]
+    fn waits_for_pixel_counts_with_a_budget() {
+        let mut queue = CompressionQueue::new(jobs(3), 4, Some(100));
+        assert!(queue.has_running());
+        assert!(queue.start_ready().is_empty());
+        assert_eq!(
+            queue.inputs_to_count(),
+            Some(vec!["0.png".into(), "1.png".into(), "2.png".into()])
+        );
+
+        queue.set_pixel_counts(vec![60, 40, 10]);
+        assert_eq!(queue.inputs_to_count(), None);
+        assert!(!queue.has_running());
+        assert_eq!(ids(&queue.start_ready()), [0, 1]);
+
+        // The third job would exceed the budget until a running job frees its pixels.
+        assert!(queue.start_ready().is_empty());
+        queue.finish(1);
+        assert_eq!(ids(&queue.start_ready()), [2]);
+    }
+
+    #[test]
+    fn job_larger_than_the_budget_runs_alone() {
+        let mut queue = CompressionQueue::new(jobs(3), 4, Some(100));
+        queue.set_pixel_counts(vec![10, 500, 10]);
+        assert_eq!(ids(&queue.start_ready()), [0]);
+
+        queue.finish(0);
+        assert_eq!(ids(&queue.start_ready()), [1]);
+        assert!(queue.start_ready().is_empty());
+
+        queue.finish(1);
+        assert_eq!(ids(&queue.start_ready()), [2]);
+    }
+
+    #[test]
+    fn missing_pixel_counts_count_as_zero() {
+        let mut queue = CompressionQueue::new(jobs(3), 4, Some(100));
+        queue.set_pixel_counts(vec![100]);
+        assert_eq!(ids(&queue.start_ready()), [0, 1, 2]);
+    }
+
+    #[test]
+    fn finishing_an_unknown_job_changes_nothing() {
+        let mut queue = CompressionQueue::new(jobs(2), 4, Some(100));
+        queue.set_pixel_counts(vec![100, 100]);
+        assert_eq!(ids(&queue.start_ready()), [0]);
+
+        queue.finish(1);
+        queue.finish(7);
+        assert!(queue.start_ready().is_empty());
+        queue.finish(0);
+        queue.finish(0);
+        assert_eq!(ids(&queue.start_ready()), [1]);
+    }
+
+    #[test]
+    fn paused_queue_starts_nothing() {
+        let mut queue = CompressionQueue::new(jobs(3), 1, None);
+        assert_eq!(ids(&queue.start_ready()), [0]);
+
+        queue.set_paused(true);
+        assert!(queue.is_paused());
+        queue.finish(0);
+        assert!(queue.start_ready().is_empty());
+        assert!(!queue.has_running());
+
+        queue.set_paused(false);
+        assert!(!queue.is_paused());
+        assert_eq!(ids(&queue.start_ready()), [1]);
+    }
+
+    #[test]
+    fn cleared_queue_only_finishes_running_jobs() {
+        let mut queue = CompressionQueue::new(jobs(3), 1, None);
+        assert_eq!(ids(&queue.start_ready()), [0]);
+
+        queue.clear_pending();
+        assert!(queue.has_running());
+        queue.finish(0);
+        assert!(queue.start_ready().is_empty());
+        assert!(!queue.has_running());
+    }
+}
@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+
+/// The template used when none is configured, matching the historical output names.
+pub const DEFAULT_TEMPLATE: &str = "{stem}_compressed.{ext}";
+
+/// A placeholder that can be used in an output file name template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Token {
+    /// The source file name without its extension.
+    Stem,
+    /// The extension of the output format.
+    Ext,
+    /// The name of the output format.
+    Format,
+    /// The output width in pixels.
+    Width,
+    /// The output height in pixels.
+    Height,
+    /// The configured quality.
+    Quality,
+    /// The current date as `YYYY-MM-DD`.
+    Date,
+    /// The 1-based position of the file in the batch.
+    Index,
+    /// The first eight hex digits of a checksum of the source file.
+    Hash8,
+}
+
+impl Token {
+    pub const ALL: [Token; 9] = [
+        Self::Stem,
+        Self::Ext,
+        Self::Format,
+        Self::Width,
+        Self::Height,
+        Self::Quality,
+        Self::Date,
+        Self::Index,
+        Self::Hash8,
+    ];
+
+    /// Returns the name of this token as written between braces in a template.
+    ///
+    /// # Returns
+    ///
+    /// A static string slice containing the token name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Stem => "stem",
+            Self::Ext => "ext",
+            Self::Format => "format",
+            Self::Width => "width",
+            Self::Height => "height",
+            Self::Quality => "quality",
+            Self::Date => "date",
+            Self::Index => "index",
+            Self::Hash8 => "hash8",
+        }
+    }
+}
+
+/// A literal piece of text or a token within a parsed template.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum Segment {
+    Literal(String),
+    Token(Token),
+}
+
+/// A parsed output file name template, such as `{stem}_compressed.{ext}`.
+///
+/// Tokens are written between braces; a literal brace is written as `{{` or `}}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileNameTemplate {
+    segments: Vec<Segment>,
+}
+
+impl FileNameTemplate {
+    /// Parses a template string.
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - The template to parse.
+    ///
+    /// # Returns
+    ///
+    /// The parsed template, or an error message if the template is empty, contains an unknown
+    /// or unterminated token, contains a path separator, or is `.` or `..`.
+    pub fn parse(template: &str) -> Result<Self, String> {
+        if template.trim().is_empty() {
+            return Err("The file name template is empty".to_string());
+        }
+        if template.contains(['/', '\\']) {
+            return Err("The file name template may not contain path separators".to_string());
+        }
+        // Tokens render to at least one character; names they turn into `.` or `..` are
+        // handled when rendering.
+        if template == "." || template == ".." {
+            return Err(format!("The file name template may not be '{template}'"));
+        }
+
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err("Unterminated token in file name template".to_string());
+                            }
+                        }
+                    }
+                    let token = Token::ALL
+                        .into_iter()
+                        .find(|t| t.name() == name)
+                        .ok_or_else(|| {
+                            format!("Unknown token '{{{name}}}' in file name template")
+                        })?;
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Token(token));
+                }
+                '}' => return Err("Unmatched '}' in file name template".to_string()),
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Checks whether the template contains the given token.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token to look for.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the token appears in the template, `false` otherwise.
+    pub fn uses(&self, token: Token) -> bool {
+        self.segments.contains(&Segment::Token(token))
+    }
+
+    /// Renders the template into a file name.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Returns the value of each token used in the template.
+    ///
+    /// # Returns
+    ///
+    /// The rendered file name. A name that would be `.` or `..`, e.g. `{stem}` of `..png`, gets
+    /// an `_` appended so it never names a folder.
+    pub fn render(&self, mut value: impl FnMut(Token) -> String) -> String {
+        let mut name: String = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(text) => text.clone(),
+                Segment::Token(token) => value(*token),
+            })
+            .collect();
+        if name.is_empty() || name == "." || name == ".." {
+            name.push('_');
+        }
+        name
+    }
+}
+
+impl Default for FileNameTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_TEMPLATE).expect("the default template is valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders `template` with fixed token values.
+    fn render(template: &str, stem: &str) -> String {
+        FileNameTemplate::parse(template)
+            .unwrap()
+            .render(|token| match token {
+                Token::Stem => stem.to_string(),
+                Token::Ext => "webp".to_string(),
+                Token::Format => "WebP".to_string(),
+                Token::Width => "640".to_string(),
+                Token::Height => "480".to_string(),
+                Token::Quality => "80".to_string(),
+                Token::Date => "2026-10-18".to_string(),
+                Token::Index => "3".to_string(),
+                Token::Hash8 => "0badf00d".to_string(),
+            })
+    }
+
+    #[test]
+    fn renders_every_token() {
+        assert_eq!(render(DEFAULT_TEMPLATE, "photo"), "photo_compressed.webp");
+        assert_eq!(
+            render(
+                "{index}-{stem}-{width}x{height}-q{quality}-{date}-{hash8}-{format}.{ext}",
+                "photo"
+            ),
+            "3-photo-640x480-q80-2026-10-18-0badf00d-WebP.webp"
+        );
+        assert_eq!(
+            FileNameTemplate::default(),
+            FileNameTemplate::parse(DEFAULT_TEMPLATE).unwrap()
+        );
+    }
+
+    #[test]
+    fn escaped_braces_are_literal() {
+        assert_eq!(render("{{{stem}}}.{ext}", "photo"), "{photo}.webp");
+        assert_eq!(render("{{stem}}", "photo"), "{stem}");
+        assert!(
+            !FileNameTemplate::parse("{{stem}}")
+                .unwrap()
+                .uses(Token::Stem)
+        );
+        assert!(
+            FileNameTemplate::parse("{{{stem}}}")
+                .unwrap()
+                .uses(Token::Stem)
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_unterminated_tokens() {
+        assert_eq!(
+            FileNameTemplate::parse("{name}.{ext}").unwrap_err(),
+            "Unknown token '{name}' in file name template"
+        );
+        assert_eq!(
+            FileNameTemplate::parse("{}.{ext}").unwrap_err(),
+            "Unknown token '{}' in file name template"
+        );
+        assert_eq!(
+            FileNameTemplate::parse("{stem").unwrap_err(),
+            "Unterminated token in file name template"
+        );
+        assert_eq!(
+            FileNameTemplate::parse("stem}").unwrap_err(),
+            "Unmatched '}' in file name template"
+        );
+    }
+
+    #[test]
+    fn rejects_templates_naming_a_folder() {
+        for template in ["", "  ", ".", ".."] {
+            assert!(FileNameTemplate::parse(template).is_err(), "{template:?}");
+        }
+        for template in ["a/{stem}", "../{stem}", "{stem}\\b", "/{stem}"] {
+            assert_eq!(
+                FileNameTemplate::parse(template).unwrap_err(),
+                "The file name template may not contain path separators"
+            );
+        }
+    }
+
+    #[test]
+    fn rendered_dot_names_stay_files() {
+        // `..png` has the stem `.`, and `...png` the stem `..`.
+        assert_eq!(render("{stem}", "."), "._");
+        assert_eq!(render("{stem}", ".."), ".._");
+        assert_eq!(render("{stem}.", "."), ".._");
+        assert_eq!(render("{stem}", ""), "_");
+        assert_eq!(render("...", "photo"), "...");
+    }
+}
@@ -1,27 +1,54 @@
+use crate::services::image_service::BACKUP_FOLDER_NAME;
 use std::collections::HashSet;
 use std::path::PathBuf;
 
-pub(crate) const IMAGE_EXTENSIONS: &[&str] =
-    &["png", "jpg", "jpeg", "bmp", "gif", "webp", "tiff", "tif"];
+pub(crate) const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "bmp", "gif", "webp", "tiff", "tif", "jxl",
+];
+
+/// The image files found by [`scan_folder`], relative to the folder that was scanned.
+#[derive(Debug, Clone)]
+pub struct ScanResult {
+    /// The folder that was scanned.
+    pub root: PathBuf,
+    /// The paths of the image files found, relative to `root`.
+    pub relative_paths: Vec<PathBuf>,
+}
+
+impl ScanResult {
+    /// Returns the full paths of the image files found.
+    ///
+    /// # Returns
+    ///
+    /// A vector of file paths (as strings), each joined onto the scan root.
+    pub fn paths(&self) -> Vec<String> {
+        self.relative_paths
+            .iter()
+            .map(|p| self.root.join(p).to_string_lossy().into_owned())
+            .collect()
+    }
+}
 
 /// Scans a folder to find image files based on a predefined set of valid image extensions.
 ///
+/// Backup folders created by in-place compression are not descended into.
+///
 /// # Arguments
 /// * `folder`: A `PathBuf` representing the folder to scan.
 /// * `recursive`: A `bool` indicating whether the scan should include subdirectories recursively.
 ///
 /// # Returns
-/// * `Ok(Vec<String>)`: A vector of file paths (as strings) pointing to image files found
-///   in the folder (and subdirectories, if `recursive` is `true`).
+/// * `Ok(ScanResult)`: The scanned folder along with the paths, relative to it, of the image
+///   files found in the folder (and subdirectories, if `recursive` is `true`).
 /// * `Err(String)`: An error message indicating why the scan could not be completed. This could
 ///   include issues such as failure to read a folder, access directory entries, or fetch metadata
 ///   for entries.
-pub fn scan_folder(folder: PathBuf, recursive: bool) -> Result<Vec<String>, String> {
+pub fn scan_folder(folder: PathBuf, recursive: bool) -> Result<ScanResult, String> {
     let mut entry_errors: Vec<String> = Vec::new();
-    let mut files: Vec<String> = Vec::new();
+    let mut files: Vec<PathBuf> = Vec::new();
 
     if recursive {
-        let mut dirs: Vec<PathBuf> = vec![folder];
+        let mut dirs: Vec<PathBuf> = vec![folder.clone()];
         let mut visited: HashSet<PathBuf> = HashSet::new();
 
         while let Some(dir) = dirs.pop() {
@@ -49,6 +76,9 @@ pub fn scan_folder(folder: PathBuf, recursive: bool) -> Result<Vec<String>, Stri
                         };
 
                         if metadata.is_dir() {
+                            if entry.file_name() == BACKUP_FOLDER_NAME {
+                                continue;
+                            }
                             let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
                             if visited.insert(canonical) {
                                 dirs.push(path);
@@ -56,8 +86,9 @@ pub fn scan_folder(folder: PathBuf, recursive: bool) -> Result<Vec<String>, Stri
                         } else if metadata.is_file()
                             && let Some(ext) = path.extension().and_then(|e| e.to_str())
                             && IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str())
+                            && let Ok(relative) = path.strip_prefix(&folder)
                         {
-                            files.push(path.to_string_lossy().into_owned());
+                            files.push(relative.to_path_buf());
                         }
                     }
                 }
@@ -85,7 +116,7 @@ pub fn scan_folder(folder: PathBuf, recursive: bool) -> Result<Vec<String>, Stri
                         && let Some(ext_str) = ext.to_str()
                         && IMAGE_EXTENSIONS.contains(&ext_str.to_lowercase().as_str())
                     {
-                        files.push(path.to_string_lossy().into_owned());
+                        files.push(PathBuf::from(entry.file_name()));
                     }
                 }
             }
@@ -96,5 +127,8 @@ pub fn scan_folder(folder: PathBuf, recursive: bool) -> Result<Vec<String>, Stri
         return Err(entry_errors.join("\n"));
     }
 
-    Ok(files)
+    Ok(ScanResult {
+        root: folder,
+        relative_paths: files,
+    })
 }
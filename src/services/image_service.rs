@@ -1,16 +1,23 @@
+use crate::services::animation::Animation;
+use crate::services::file_name_template::{FileNameTemplate, Token};
+use crate::services::{jpeg_lossless, jpeg_xl, similarity};
 use image::{DynamicImage, ExtendedColorType, GenericImageView, ImageEncoder, ImageFormat};
 use img_parts::ImageEXIF;
+use log::info;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fs;
-use std::io::Cursor;
-use std::path::Path;
+use std::io::{Cursor, ErrorKind, Read};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 #[derive(Clone)]
 pub struct ImageService;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum OutputFormat {
     Jpeg,
     Png,
@@ -18,20 +25,102 @@ pub enum OutputFormat {
     WebP,
     Bmp,
     Tiff,
+    Avif,
+    JpegXl,
+    /// Keep each input's own format, subject to the per-source format mapping.
+    Original,
 }
 
 impl OutputFormat {
-    pub const ALL: [OutputFormat; 6] = [
+    pub const ALL: [OutputFormat; 9] = [
         Self::Jpeg,
         Self::Png,
         Self::Gif,
         Self::WebP,
         Self::Bmp,
         Self::Tiff,
+        Self::Avif,
+        Self::JpegXl,
+        Self::Original,
     ];
 
+    /// The formats that have an encoder of their own, i.e. every format except `Original`.
+    pub const ENCODABLE: [OutputFormat; 8] = [
+        Self::Jpeg,
+        Self::Png,
+        Self::Gif,
+        Self::WebP,
+        Self::Bmp,
+        Self::Tiff,
+        Self::Avif,
+        Self::JpegXl,
+    ];
+
+    /// The input formats that can be mapped to an output format when keeping the original format.
+    pub const SOURCES: [OutputFormat; 7] = [
+        Self::Jpeg,
+        Self::Png,
+        Self::Gif,
+        Self::WebP,
+        Self::Bmp,
+        Self::Tiff,
+        Self::JpegXl,
+    ];
+
+    /// Returns the output format matching a detected input format.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The input format, as detected by `image::guess_format`.
+    ///
+    /// # Returns
+    ///
+    /// The matching `OutputFormat`, or `None` when no encoder exists for the input format.
+    pub fn from_image_format(format: ImageFormat) -> Option<Self> {
+        match format {
+            ImageFormat::Jpeg => Some(Self::Jpeg),
+            ImageFormat::Png => Some(Self::Png),
+            ImageFormat::Gif => Some(Self::Gif),
+            ImageFormat::WebP => Some(Self::WebP),
+            ImageFormat::Bmp => Some(Self::Bmp),
+            ImageFormat::Tiff => Some(Self::Tiff),
+            ImageFormat::Avif => Some(Self::Avif),
+            _ => None,
+        }
+    }
+
+    /// Detects the format of an input from its leading bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The leading bytes of the input file.
+    ///
+    /// # Returns
+    ///
+    /// The matching `OutputFormat`, or `None` when the format is unknown or has no encoder.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if jpeg_xl::is_jpeg_xl(bytes) {
+            return Some(Self::JpegXl);
+        }
+        image::guess_format(bytes)
+            .ok()
+            .and_then(Self::from_image_format)
+    }
+
+    /// Returns the default per-source format mapping, which keeps every format as is.
+    ///
+    /// # Returns
+    ///
+    /// A map from each source format to itself.
+    pub fn default_mapping() -> BTreeMap<OutputFormat, OutputFormat> {
+        Self::SOURCES.into_iter().map(|f| (f, f)).collect()
+    }
+
     /// Returns the canonical file extension for this format.
     ///
+    /// `Original` has no extension of its own; resolve it per file with
+    /// [`CompressionParams::resolve_format`] first.
+    ///
     /// # Returns
     ///
     /// A static string slice representing the file extension for the given format.
@@ -43,6 +132,56 @@ impl OutputFormat {
             Self::WebP => "webp",
             Self::Bmp => "bmp",
             Self::Tiff => "tiff",
+            Self::Avif => "avif",
+            Self::JpegXl => "jxl",
+            Self::Original => "",
+        }
+    }
+
+    /// Returns whether the quality setting has an effect on this format.
+    ///
+    /// # Arguments
+    ///
+    /// * `lossy_png` - Whether lossy PNG is enabled, in which case a PNG quality below 100
+    ///   enables palette quantization.
+    ///
+    /// # Returns
+    ///
+    /// `true` for lossy formats whose encoder accepts a quality value, and for PNG when
+    /// `lossy_png` is set. `false` otherwise. `Original` reports `true` because inputs may be
+    /// mapped to any of these formats.
+    pub fn supports_quality(self, lossy_png: bool) -> bool {
+        match self {
+            Self::Jpeg | Self::WebP | Self::Avif | Self::Original => true,
+            Self::Png => lossy_png,
+            _ => false,
+        }
+    }
+
+    /// Returns whether the lowest quality keeping a minimum similarity can be searched for this
+    /// format.
+    ///
+    /// AVIF outputs cannot be decoded here to measure their similarity, so they always use the
+    /// configured quality instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `lossy_png` - Whether lossy PNG is enabled, see [`OutputFormat::supports_quality`].
+    /// * `mapping` - The per-source format mapping that `Original` resolves through.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the format supports quality and is not AVIF. `Original` reports `true` unless
+    /// an input may be mapped to AVIF.
+    pub fn supports_similarity(
+        self,
+        lossy_png: bool,
+        mapping: &BTreeMap<OutputFormat, OutputFormat>,
+    ) -> bool {
+        match self {
+            Self::Avif => false,
+            Self::Original => !mapping.values().any(|&target| target == Self::Avif),
+            format => format.supports_quality(lossy_png),
         }
     }
 }
@@ -65,22 +204,281 @@ impl std::fmt::Display for OutputFormat {
             OutputFormat::WebP => write!(f, "WebP"),
             OutputFormat::Bmp => write!(f, "BMP"),
             OutputFormat::Tiff => write!(f, "Tiff"),
+            OutputFormat::Avif => write!(f, "AVIF"),
+            OutputFormat::JpegXl => write!(f, "JPEG XL"),
+            OutputFormat::Original => write!(f, "Original"),
         }
     }
 }
 
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    /// Parses an `OutputFormat` from its display name or file extension, case-insensitively.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The format name or extension (e.g. `"jpeg"`, `"jpg"`, `"WebP"`).
+    ///
+    /// # Returns
+    ///
+    /// The matching `OutputFormat`, or an error message if the name is not recognized.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        match lower.as_str() {
+            "jpeg" | "jpg" => Ok(Self::Jpeg),
+            "tif" => Ok(Self::Tiff),
+            "jpegxl" => Ok(Self::JpegXl),
+            "original" => Ok(Self::Original),
+            _ => Self::ENCODABLE
+                .into_iter()
+                .find(|f| f.extension() == lower || f.to_string().to_lowercase() == lower)
+                .ok_or_else(|| format!("Unsupported output format '{s}'")),
+        }
+    }
+}
+
+/// How the chroma channels of a JPEG are sampled relative to the luma channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChromaSubsampling {
+    /// Full chroma resolution.
+    Yuv444,
+    /// Half the horizontal chroma resolution.
+    Yuv422,
+    /// Half the horizontal and vertical chroma resolution.
+    Yuv420,
+}
+
+impl ChromaSubsampling {
+    pub const ALL: [ChromaSubsampling; 3] = [Self::Yuv444, Self::Yuv422, Self::Yuv420];
+
+    /// Returns the size of a chroma sample in luma pixels, as `(horizontal, vertical)`.
+    ///
+    /// # Returns
+    ///
+    /// The chroma pixel size, e.g. `(2, 2)` for 4:2:0.
+    fn pixel_size(self) -> (u8, u8) {
+        match self {
+            Self::Yuv444 => (1, 1),
+            Self::Yuv422 => (2, 1),
+            Self::Yuv420 => (2, 2),
+        }
+    }
+}
+
+impl std::fmt::Display for ChromaSubsampling {
+    /// Formats the subsampling in the usual `J:a:b` notation.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - A mutable reference to a `Formatter` where the formatted string will be written.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating whether the formatting was successful or if an error occurred.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Yuv444 => write!(f, "4:4:4"),
+            Self::Yuv422 => write!(f, "4:2:2"),
+            Self::Yuv420 => write!(f, "4:2:0"),
+        }
+    }
+}
+
+impl std::str::FromStr for ChromaSubsampling {
+    type Err = String;
+
+    /// Parses a subsampling from its `J:a:b` notation, with or without the colons.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The subsampling, e.g. `"4:2:0"` or `"420"`.
+    ///
+    /// # Returns
+    ///
+    /// The matching `ChromaSubsampling`, or an error message if it is not recognized.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.to_string() == s || c.to_string().replace(':', "") == s)
+            .ok_or_else(|| format!("Unsupported chroma subsampling '{s}'"))
+    }
+}
+
+/// Advanced options for JPEG output.
+///
+/// The default options produce a baseline JPEG with 4:2:0 subsampling through the `image`
+/// encoder. Any other combination is encoded with mozjpeg.
+///
+/// In lossless mode, JPEG inputs whose dimensions stay the same are recompressed without
+/// decoding them to pixels, so quality, target size and similarity settings do not apply to
+/// them. Other inputs are encoded as usual.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JpegOptions {
+    /// Whether to write a progressive JPEG, which loads gradually and is usually smaller.
+    pub progressive: bool,
+    /// How the chroma channels are sampled.
+    pub chroma_subsampling: ChromaSubsampling,
+    /// Whether to compute Huffman tables for the image instead of using the standard ones.
+    pub optimize_huffman: bool,
+    /// Whether to use trellis quantization, which trades encoding time for smaller files.
+    pub trellis: bool,
+    /// Whether to recompress JPEG inputs losslessly, keeping their DCT coefficients intact.
+    pub lossless: bool,
+}
+
+impl Default for JpegOptions {
+    fn default() -> Self {
+        Self {
+            progressive: false,
+            chroma_subsampling: ChromaSubsampling::Yuv420,
+            optimize_huffman: false,
+            trellis: false,
+            lossless: false,
+        }
+    }
+}
+
+/// What to do when the compressed output is larger than the source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LargerOutputPolicy {
+    /// Write the larger output regardless.
+    WriteAnyway,
+    /// Write the original bytes to the output path instead.
+    KeepOriginal,
+    /// Do not write any output for the file.
+    Skip,
+}
+
+impl LargerOutputPolicy {
+    pub const ALL: [LargerOutputPolicy; 3] = [Self::WriteAnyway, Self::KeepOriginal, Self::Skip];
+}
+
+/// What to do when an output file already exists on disk, e.g. from a previous run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExistingFilePolicy {
+    /// Replace the existing file.
+    Overwrite,
+    /// Leave the existing file alone and write no output.
+    Skip,
+    /// Write the output next to the existing file with a numeric suffix.
+    Rename,
+    /// Let the user choose one of the other policies before the batch starts. A batch that
+    /// reaches [`ImageService::compress_single`] unresolved skips existing files.
+    Ask,
+}
+
+impl ExistingFilePolicy {
+    pub const ALL: [ExistingFilePolicy; 4] = [Self::Overwrite, Self::Skip, Self::Rename, Self::Ask];
+}
+
+/// Name of the folder that in-place compression copies originals into when backing up next to
+/// the originals.
+pub const BACKUP_FOLDER_NAME: &str = ".compressr-backup";
+
+/// The output bytes, chosen quality and final dimensions of a target-size encode.
+type TargetSizeEncoding = (Vec<u8>, u8, (u32, u32));
+
+/// Where originals are copied before in-place compression replaces them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BackupTarget {
+    /// A [`BACKUP_FOLDER_NAME`] folder next to each original.
+    Sibling,
+    /// A single folder shared by the whole batch.
+    Directory(Arc<Path>),
+}
+
+/// What the output path of a source file depends on, read by
+/// [`ImageService::read_source_info`].
+#[derive(Debug, Clone, Default)]
+pub struct SourceInfo {
+    /// The detected format of the source.
+    pub format: Option<OutputFormat>,
+    /// The source dimensions in pixels.
+    pub dimensions: Option<(u32, u32)>,
+    /// The CRC-32 checksum of the source file, for `{hash8}`.
+    pub checksum: Option<u32>,
+}
+
+/// The action taken for the output of a single compression operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputAction {
+    /// The compressed image was written.
+    Written,
+    /// The compressed image was larger, so the original bytes were written instead.
+    KeptOriginal,
+    /// The compressed image was larger, so no output was written.
+    Skipped,
+    /// The output file already existed and was left untouched.
+    SkippedExisting,
+}
+
 /// Result of a single image compression operation.
 #[derive(Debug, Clone)]
 pub struct CompressionResult {
+    /// The path of the source file, as it was passed in.
+    pub source_path: String,
     /// The original file name (without directory).
     pub file_name: String,
     /// Original file size in bytes.
     pub original_size: u64,
-    /// Compressed file size in bytes.
+    /// Compressed file size in bytes. Equals `original_size` when the original was kept or
+    /// the output was skipped.
     pub compressed_size: u64,
+    /// The action taken for the output file.
+    pub action: OutputAction,
+    /// The path the output was written to, or `None` when the output was skipped.
+    pub output_path: Option<String>,
+    /// Quality picked by the target size or similarity search, when one was performed.
+    pub chosen_quality: Option<u8>,
+    /// Achieved structural similarity (0.0–1.0) when a similarity search was performed.
+    pub similarity: Option<f64>,
+    /// Whether the output was given a numeric suffix because the file already existed.
+    pub renamed: bool,
+    /// How long each phase of the compression took.
+    pub timings: PhaseTimings,
+}
+
+/// Time spent in each phase of compressing a single file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PhaseTimings {
+    /// Reading and decoding the source file, including its EXIF data.
+    pub decode: Duration,
+    /// Resizing the decoded image.
+    pub geometry: Duration,
+    /// Encoding the output, including any quality search.
+    pub encode: Duration,
+    /// Writing, verifying and backing up files.
+    pub write: Duration,
+}
+
+impl PhaseTimings {
+    /// Returns the time spent across all phases.
+    ///
+    /// # Returns
+    ///
+    /// The sum of the phase durations.
+    pub fn total(&self) -> Duration {
+        self.decode + self.geometry + self.encode + self.write
+    }
 }
 
 impl CompressionResult {
+    /// Checks whether the original may be removed, i.e. its compressed output was written to a
+    /// separate file. Written outputs have always passed verification.
+    ///
+    /// # Returns
+    ///
+    /// `true` when the compressed output was written to a path other than the source, `false`
+    /// when it was skipped, the original was kept, or the source itself was replaced.
+    pub fn can_remove_original(&self) -> bool {
+        self.action == OutputAction::Written
+            && self
+                .output_path
+                .as_deref()
+                .is_some_and(|p| Path::new(p) != Path::new(&self.source_path))
+    }
+
     /// Returns the percentage of size changed.
     ///
     /// # Returns
@@ -96,7 +494,7 @@ impl CompressionResult {
 }
 
 /// Parameters for a single image compression operation.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompressionParams {
     /// Base output path (directory or file). Shared across batch items via `Arc`.
     pub output_path: Arc<str>,
@@ -108,8 +506,53 @@ pub struct CompressionParams {
     pub width: Option<u32>,
     /// Desired output height in pixels (optional).
     pub height: Option<u32>,
-    /// JPEG/WEBP Image quality (0–100); ignored for other formats.
+    /// JPEG/WEBP/AVIF Image quality (0–100). For PNG, values below 100 quantize the image to
+    /// a palette when `lossy_png` is set; ignored for other formats.
     pub quality: u8,
+    /// Whether PNG outputs are quantized to a palette when `quality` is below 100.
+    pub lossy_png: bool,
+    /// AVIF encoder speed (1–10); lower values are slower but compress better.
+    pub avif_speed: u8,
+    /// PNG optimization level (0–6); higher levels try more filter and reduction strategies.
+    pub png_optimization_level: u8,
+    /// Whether PNG data is recompressed with Zopfli instead of libdeflate (much slower).
+    pub png_zopfli: bool,
+    /// Maximum frame rate of animated GIF and WebP outputs (optional); faster animations drop
+    /// frames.
+    pub max_frame_rate: Option<u32>,
+    /// Whether animated GIF outputs share one optimized palette instead of a palette per
+    /// frame.
+    pub optimize_gif_palette: bool,
+    /// Maximum output size in bytes (optional). When set, the quality is searched instead of
+    /// using `quality`; ignored for formats without a quality setting.
+    pub target_size: Option<u64>,
+    /// Whether the image may be downscaled when the target size cannot be reached at the
+    /// lowest quality.
+    pub downscale_to_target_size: bool,
+    /// Minimum structural similarity (0.0–1.0) to the source image (optional). When set, the
+    /// lowest quality meeting it is searched; `target_size` takes precedence. Ignored for AVIF
+    /// output, which cannot be decoded for comparison, so `quality` is used instead.
+    pub min_similarity: Option<f64>,
+    /// What to do when the compressed output is larger than the source file.
+    pub larger_output_policy: LargerOutputPolicy,
+    /// What to do when the output file already exists on disk.
+    pub existing_file_policy: ExistingFilePolicy,
+    /// Output format per source format, used when `format` is `Original`. Sources without an
+    /// entry keep their own format. Shared across batch items via `Arc`.
+    pub format_mapping: Arc<BTreeMap<OutputFormat, OutputFormat>>,
+    /// When set, each input's folder relative to this root is recreated below the output
+    /// directory instead of writing every file into the output directory itself, and below
+    /// the backup folder of in-place compression.
+    pub source_root: Option<Arc<Path>>,
+    /// Template for output file names when writing into an output directory.
+    pub file_name_template: Arc<FileNameTemplate>,
+    /// When set, outputs replace their source files (keeping the source name with the output
+    /// format's extension) after the originals are backed up here; `output_path` is ignored.
+    /// A format change never replaces another file; `existing_file_policy` is treated as
+    /// [`ExistingFilePolicy::Rename`].
+    pub in_place_backup: Option<BackupTarget>,
+    /// Advanced options for JPEG output.
+    pub jpeg_options: JpegOptions,
     /// Output container format.
     pub format: OutputFormat,
     /// Whether to copy EXIF metadata from the source file.
@@ -119,6 +562,31 @@ pub struct CompressionParams {
     pub output_path_override: Option<String>,
 }
 
+impl CompressionParams {
+    /// Resolves the concrete output format for an input of the given source format.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The detected format of the input file, if known.
+    ///
+    /// # Returns
+    ///
+    /// `format` itself unless it is `Original`; otherwise the mapped format for `source`,
+    /// falling back to the source format itself and to PNG for unknown sources.
+    pub fn resolve_format(&self, source: Option<OutputFormat>) -> OutputFormat {
+        if self.format != OutputFormat::Original {
+            return self.format;
+        }
+
+        let source = source.unwrap_or(OutputFormat::Png);
+        self.format_mapping
+            .get(&source)
+            .copied()
+            .filter(|f| *f != OutputFormat::Original)
+            .unwrap_or(source)
+    }
+}
+
 impl ImageService {
     /// Initialize a new ImageService
     ///
@@ -156,6 +624,7 @@ impl ImageService {
             return Err("Height cannot be equal to 0".to_string());
         }
 
+        let decode_started = Instant::now();
         let raw = fs::read(&file).map_err(|e| format!("Failed to read '{file}': {e}"))?;
 
         if cancelled.load(Ordering::Relaxed) {
@@ -164,48 +633,227 @@ impl ImageService {
 
         let original_size = raw.len() as u64;
 
+        // Pin `Original` to the concrete format of this input so everything below can
+        // dispatch on a single encoder.
+        let resolved_params;
+        let params = if params.format == OutputFormat::Original {
+            resolved_params = CompressionParams {
+                format: params.resolve_format(OutputFormat::detect(&raw)),
+                ..params.clone()
+            };
+            &resolved_params
+        } else {
+            params
+        };
+
         let source_exif = if params.preserve_exif {
             self.read_exif(&raw)
         } else {
             None
         };
-        let img = image::load_from_memory(&raw)
-            .map_err(|e| format!("Failed to load image '{file}': {e}"))?;
 
-        if cancelled.load(Ordering::Relaxed) {
-            return Err("COMPRESSION_ABORTED".to_string());
-        }
+        // A JPEG that keeps its dimensions can be recompressed without decoding it to pixels.
+        let mut timings = PhaseTimings::default();
+        let recompressed = match self.lossless_jpeg_dimensions(&raw, params) {
+            Some(dimensions) => {
+                timings.decode = decode_started.elapsed();
+                let encode_started = Instant::now();
+                let bytes = self.recompress_jpeg(&raw, params, source_exif.as_ref())?;
+                timings.encode = encode_started.elapsed();
+                bytes.map(|bytes| (bytes, dimensions))
+            }
+            None => None,
+        };
+        let (final_bytes, chosen_quality, similarity, dimensions, source_dimensions) =
+            if let Some((bytes, dimensions)) = recompressed {
+                (bytes, None, None, dimensions, dimensions)
+            } else if let Some(mut animation) = self.decode_animation(&raw, params)? {
+                if cancelled.load(Ordering::Relaxed) {
+                    return Err("COMPRESSION_ABORTED".to_string());
+                }
 
-        let img = self.apply_geometry(img, params);
+                let source_dimensions = animation.dimensions();
+                timings.decode = decode_started.elapsed();
 
-        // Encode to an in-memory buffer — no intermediate file write needed.
-        let encoded = self.encode(&img, params)?;
+                let geometry_started = Instant::now();
+                for frame in &mut animation.frames {
+                    let image = DynamicImage::ImageRgba8(std::mem::take(&mut frame.image));
+                    frame.image = self.apply_geometry(image, params).into_rgba8();
+                }
+                timings.geometry = geometry_started.elapsed();
+
+                let encode_started = Instant::now();
+                let bytes = self.encode_animation(&animation, params, source_exif.as_ref())?;
+                timings.encode = encode_started.elapsed();
+                (bytes, None, None, animation.dimensions(), source_dimensions)
+            } else {
+                let img = image::load_from_memory(&raw)
+                    .map_err(|e| format!("Failed to load image '{file}': {e}"))?;
+
+                if cancelled.load(Ordering::Relaxed) {
+                    return Err("COMPRESSION_ABORTED".to_string());
+                }
+
+                let source_dimensions = img.dimensions();
+                timings.decode = decode_started.elapsed();
+
+                let geometry_started = Instant::now();
+                let img = self.apply_geometry(img, params);
+                timings.geometry = geometry_started.elapsed();
+
+                // Encode to an in-memory buffer — no intermediate file write needed.
+                let encode_started = Instant::now();
+                let (bytes, chosen_quality, similarity, dimensions) =
+                    match (params.target_size, params.min_similarity) {
+                        (Some(target), _) if params.format.supports_quality(params.lossy_png) => {
+                            let (bytes, quality, dimensions) = self.encode_to_target_size(
+                                img,
+                                params,
+                                source_exif.as_ref(),
+                                target,
+                                &cancelled,
+                            )?;
+                            (bytes, Some(quality), None, dimensions)
+                        }
+                        (_, Some(min_similarity))
+                            if params
+                                .format
+                                .supports_similarity(params.lossy_png, &params.format_mapping) =>
+                        {
+                            let (bytes, quality, score) = self.encode_to_min_similarity(
+                                &img,
+                                params,
+                                source_exif.as_ref(),
+                                min_similarity,
+                                &cancelled,
+                            )?;
+                            (bytes, Some(quality), Some(score), img.dimensions())
+                        }
+                        _ => (
+                            self.encode_with_exif(&img, params, source_exif.as_ref())?,
+                            None,
+                            None,
+                            img.dimensions(),
+                        ),
+                    };
+                timings.encode = encode_started.elapsed();
+                (
+                    bytes,
+                    chosen_quality,
+                    similarity,
+                    dimensions,
+                    source_dimensions,
+                )
+            };
 
         if cancelled.load(Ordering::Relaxed) {
             return Err("COMPRESSION_ABORTED".to_string());
         }
 
-        // Optionally, inject EXIF into the in-memory buffer before the single
-        // disk write, eliminating the previous read-back-from-disk round-trip.
-        let final_bytes = if let Some(exif) = source_exif {
-            self.inject_exif(encoded, exif, params.format)?
+        let write_started = Instant::now();
+
+        let action = if final_bytes.len() as u64 <= original_size {
+            OutputAction::Written
         } else {
-            encoded
+            match params.larger_output_policy {
+                LargerOutputPolicy::WriteAnyway => OutputAction::Written,
+                LargerOutputPolicy::KeepOriginal => OutputAction::KeptOriginal,
+                LargerOutputPolicy::Skip => OutputAction::Skipped,
+            }
         };
 
-        let compressed_size = final_bytes.len() as u64;
-
         let output_path = params
             .output_path_override
             .clone()
-            .unwrap_or_else(|| self.resolve_output_path(&file, params));
+            .unwrap_or_else(|| self.resolve_output_path(&file, 0, params));
 
         if cancelled.load(Ordering::Relaxed) {
             return Err("COMPRESSION_ABORTED".to_string());
         }
 
-        fs::write(&output_path, final_bytes)
-            .map_err(|e| format!("Failed to write output file: {e}"))?;
+        // The original bytes keep their own format, so keep its extension as well.
+        let output_path = match (action, Path::new(&file).extension()) {
+            (OutputAction::KeptOriginal, Some(ext)) => Path::new(&output_path).with_extension(ext),
+            _ => PathBuf::from(&output_path),
+        };
+
+        if action != OutputAction::Skipped
+            && let Some(parent) = output_path.parent()
+        {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create output folder: {e}"))?;
+        }
+
+        // In place, the source itself is the expected output rather than an existing file that
+        // needs protecting.
+        let source = Path::new(&file);
+        let replaces_source = params.in_place_backup.is_some() && output_path == source;
+
+        // A format change in place must never replace another file, which is not backed up.
+        let existing_file_policy = if params.in_place_backup.is_some() {
+            ExistingFilePolicy::Rename
+        } else {
+            params.existing_file_policy
+        };
+
+        let (action, output_path, renamed) = if action == OutputAction::Skipped {
+            (action, None, false)
+        } else if replaces_source {
+            (action, Some(output_path), false)
+        } else {
+            match self.apply_existing_file_policy(output_path, existing_file_policy)? {
+                Some((path, renamed)) => (action, Some(path), renamed),
+                None => (OutputAction::SkippedExisting, None, false),
+            }
+        };
+
+        let (bytes, compressed_size, expected_dimensions) = match action {
+            OutputAction::Written => (
+                final_bytes.as_slice(),
+                final_bytes.len() as u64,
+                Some(dimensions),
+            ),
+            OutputAction::KeptOriginal => (raw.as_slice(), original_size, Some(source_dimensions)),
+            _ => (&[][..], original_size, None),
+        };
+        // Keeping the original in place leaves the source untouched.
+        let writes =
+            output_path.is_some() && !(replaces_source && action == OutputAction::KeptOriginal);
+        if writes && let Some(path) = &output_path {
+            // Renaming claims the output path up front; release it again on failure.
+            let release_claim = || {
+                if !replaces_source && existing_file_policy == ExistingFilePolicy::Rename {
+                    let _ = fs::remove_file(path);
+                }
+            };
+
+            let backup_path = match &params.in_place_backup {
+                Some(target) => {
+                    let backup_path = self
+                        .backup_original(source, target, params.source_root.as_deref())
+                        .inspect_err(|_| release_claim())?;
+                    info!("Backed up '{file}' to '{}'", backup_path.display());
+                    Some(backup_path)
+                }
+                None => None,
+            };
+
+            if let Err(e) = self.write_atomically(path, bytes, expected_dimensions, &cancelled) {
+                release_claim();
+                // The original was left untouched, so its backup is not needed.
+                if let Some(backup_path) = backup_path {
+                    let _ = fs::remove_file(backup_path);
+                }
+                return Err(e);
+            }
+
+            // A format change writes next to the source, which is backed up and now replaced.
+            if params.in_place_backup.is_some() && !replaces_source {
+                fs::remove_file(source)
+                    .map_err(|e| format!("Failed to remove original file: {e}"))?;
+            }
+        }
+        let output_path = output_path.map(|p| p.to_string_lossy().into_owned());
 
         let file_name = Path::new(&file)
             .file_name()
@@ -213,11 +861,388 @@ impl ImageService {
             .unwrap_or(&file)
             .to_string();
 
-        Ok(CompressionResult {
-            file_name,
-            original_size,
-            compressed_size,
-        })
+        Ok(CompressionResult {
+            source_path: file,
+            file_name,
+            original_size,
+            compressed_size,
+            action,
+            output_path,
+            chosen_quality,
+            similarity,
+            renamed,
+            timings: PhaseTimings {
+                write: write_started.elapsed(),
+                ..timings
+            },
+        })
+    }
+
+    /// Atomically writes `bytes` to `path`, verifying the written data first.
+    ///
+    /// Writes to a temporary sibling file first, verifies it with
+    /// [`verify_output`](Self::verify_output), then renames it over `path`, so an interrupted
+    /// write or a corrupt encoder output never replaces anything. The temporary file is removed
+    /// when the write or verification fails, or the batch is cancelled before the rename.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The destination path.
+    /// * `bytes`: The bytes to write.
+    /// * `expected_dimensions`: The dimensions the written image must decode to, if it can be
+    ///   decoded.
+    /// * `cancelled`: The batch cancellation flag.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the file is in place, or an error string on failure or cancellation.
+    fn write_atomically(
+        &self,
+        path: &Path,
+        bytes: &[u8],
+        expected_dimensions: Option<(u32, u32)>,
+        cancelled: &AtomicBool,
+    ) -> Result<(), String> {
+        let mut tmp_path = path.as_os_str().to_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+
+        if let Err(e) = fs::write(&tmp_path, bytes) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(format!("Failed to write output file: {e}"));
+        }
+
+        if let Err(e) = self.verify_output(&tmp_path, bytes, expected_dimensions) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+
+        if cancelled.load(Ordering::Relaxed) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err("COMPRESSION_ABORTED".to_string());
+        }
+
+        // `fs::rename` replaces an existing destination on every platform.
+        if let Err(e) = fs::rename(&tmp_path, path) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(format!("Failed to move output file into place: {e}"));
+        }
+
+        Ok(())
+    }
+
+    /// Re-reads a written output and checks that it holds the expected image.
+    ///
+    /// No AVIF decoder is built in, so AVIF outputs are checked against the dimensions their
+    /// container declares instead of being decoded; see [`avif_dimensions`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The written file.
+    /// * `expected_bytes`: The bytes that were written.
+    /// * `expected_dimensions`: The dimensions the file must decode to, or `None` to only
+    ///   compare the bytes.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the file matches, or an error string describing the mismatch.
+    fn verify_output(
+        &self,
+        path: &Path,
+        expected_bytes: &[u8],
+        expected_dimensions: Option<(u32, u32)>,
+    ) -> Result<(), String> {
+        let written = fs::read(path)
+            .map_err(|e| format!("Output verification failed: could not re-read output: {e}"))?;
+        if written != expected_bytes {
+            return Err(
+                "Output verification failed: the written file differs from the encoded image"
+                    .to_string(),
+            );
+        }
+
+        if let Some((width, height)) = expected_dimensions {
+            let (w, h) = if written.get(4..12) == Some(b"ftypavif") {
+                avif_dimensions(&written).ok_or_else(|| {
+                    "Output verification failed: could not read the AVIF container".to_string()
+                })?
+            } else {
+                image::load_from_memory(&written)
+                    .map_err(|e| {
+                        format!("Output verification failed: could not decode output: {e}")
+                    })?
+                    .dimensions()
+            };
+            if (w, h) != (width, height) {
+                return Err(format!(
+                    "Output verification failed: expected {width}x{height} pixels, got {w}x{h}"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decides where an output is written when a file may already exist at `path`.
+    ///
+    /// With [`ExistingFilePolicy::Rename`] the first free path is claimed by creating it, so
+    /// concurrent compressions in the same batch cannot pick the same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The intended output path.
+    /// * `policy`: What to do when `path` already exists.
+    ///
+    /// # Returns
+    ///
+    /// The path to write to and whether it was renamed, `None` when the output should be
+    /// skipped, or an error string if a renamed path could not be created.
+    fn apply_existing_file_policy(
+        &self,
+        path: PathBuf,
+        policy: ExistingFilePolicy,
+    ) -> Result<Option<(PathBuf, bool)>, String> {
+        match policy {
+            ExistingFilePolicy::Overwrite => Ok(Some((path, false))),
+            ExistingFilePolicy::Skip | ExistingFilePolicy::Ask => {
+                Ok((!path.exists()).then_some((path, false)))
+            }
+            ExistingFilePolicy::Rename => self.claim_unique_path(path).map(Some),
+        }
+    }
+
+    /// Claims the first free path among `path`, `path_2`, `path_3`, ... by creating it empty,
+    /// so concurrent compressions cannot pick the same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The preferred path.
+    ///
+    /// # Returns
+    ///
+    /// The claimed path and whether it differs from `path`, or an error string if no file
+    /// could be created.
+    fn claim_unique_path(&self, path: PathBuf) -> Result<(PathBuf, bool), String> {
+        let mut n: u32 = 1;
+        loop {
+            let candidate = if n == 1 {
+                path.clone()
+            } else {
+                self.with_numeric_suffix(&path, n)
+            };
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&candidate)
+            {
+                Ok(_) => return Ok((candidate, n > 1)),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => n += 1,
+                Err(e) => return Err(format!("Failed to create output file: {e}")),
+            }
+        }
+    }
+
+    /// Copies an original into its backup location before in-place compression replaces it.
+    ///
+    /// # Arguments
+    ///
+    /// * `source`: The original file.
+    /// * `target`: Where the backup is kept.
+    /// * `root`: The folder the inputs were scanned from, if any. A shared backup folder
+    ///   recreates the folders of `source` below this root so backups of equally named files
+    ///   keep their place.
+    ///
+    /// # Returns
+    ///
+    /// The path of the backup copy, or an error string if it could not be created.
+    fn backup_original(
+        &self,
+        source: &Path,
+        target: &BackupTarget,
+        root: Option<&Path>,
+    ) -> Result<PathBuf, String> {
+        let dir = match target {
+            BackupTarget::Sibling => source
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join(BACKUP_FOLDER_NAME),
+            BackupTarget::Directory(dir) => {
+                match root.and_then(|root| source.parent()?.strip_prefix(root).ok()) {
+                    Some(relative_dir) => dir.join(relative_dir),
+                    None => dir.to_path_buf(),
+                }
+            }
+        };
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create backup folder: {e}"))?;
+
+        let file_name = source
+            .file_name()
+            .ok_or_else(|| format!("Invalid source file '{}'", source.display()))?;
+        let (backup_path, _) = self.claim_unique_path(dir.join(file_name))?;
+        if let Err(e) = fs::copy(source, &backup_path) {
+            let _ = fs::remove_file(&backup_path);
+            return Err(format!("Failed to back up original file: {e}"));
+        }
+
+        Ok(backup_path)
+    }
+
+    /// Encodes `img` and, when `exif` is given, injects it into the encoded buffer.
+    ///
+    /// EXIF is injected into the in-memory buffer before the single disk write, avoiding a
+    /// read-back-from-disk round-trip.
+    ///
+    /// # Arguments
+    ///
+    /// * `img`: The image to be encoded.
+    /// * `params`: The compression parameters containing format and quality info.
+    /// * `exif`: The EXIF bytes read from the source file, if they should be preserved.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the final output bytes on success, or an error string on failure.
+    fn encode_with_exif(
+        &self,
+        img: &DynamicImage,
+        params: &CompressionParams,
+        exif: Option<&img_parts::Bytes>,
+    ) -> Result<Vec<u8>, String> {
+        let encoded = self.encode(img, params, exif.map(|e| e.as_ref()))?;
+        match exif {
+            Some(exif) => self.inject_exif(encoded, exif.clone(), params.format),
+            None => Ok(encoded),
+        }
+    }
+
+    /// Encodes `img` at the lowest quality whose decoded output is still at least
+    /// `min_similarity` similar to `img`.
+    ///
+    /// The quality is binary searched between 1 and 100; each attempt is decoded again and
+    /// compared against `img` using [`similarity::ssim`]. When no quality meets the threshold,
+    /// the quality 100 attempt is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `img`: The geometry-adjusted image to be encoded, used as the reference.
+    /// * `params`: The compression parameters containing format info.
+    /// * `exif`: The EXIF bytes to preserve.
+    /// * `min_similarity`: The minimum structural similarity (0.0–1.0) to reach.
+    /// * `cancelled`: Flag checked between encode attempts to stop early.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the output bytes, the chosen quality and the achieved similarity,
+    /// or an error string on failure.
+    fn encode_to_min_similarity(
+        &self,
+        img: &DynamicImage,
+        params: &CompressionParams,
+        exif: Option<&img_parts::Bytes>,
+        min_similarity: f64,
+        cancelled: &AtomicBool,
+    ) -> Result<(Vec<u8>, u8, f64), String> {
+        if params.format == OutputFormat::Avif {
+            return Err("Similarity search is not supported for AVIF output".to_string());
+        }
+
+        let reference = img.to_luma8();
+        let mut attempt = params.clone();
+        let mut best = None;
+        let mut last = None;
+        let (mut low, mut high) = (1u8, 100u8);
+        while low <= high {
+            if cancelled.load(Ordering::Relaxed) {
+                return Err("COMPRESSION_ABORTED".to_string());
+            }
+
+            attempt.quality = low + (high - low) / 2;
+            let bytes = self.encode_with_exif(img, &attempt, exif)?;
+            let decoded = image::load_from_memory(&bytes)
+                .map_err(|e| format!("Failed to decode encoded image: {e}"))?;
+            let score = similarity::ssim(&reference, &decoded.to_luma8());
+            if score >= min_similarity {
+                best = Some((bytes, attempt.quality, score));
+                high = attempt.quality - 1;
+            } else {
+                last = Some((bytes, attempt.quality, score));
+                low = attempt.quality + 1;
+            }
+        }
+
+        // When nothing met the threshold, the final attempt was made at quality 100.
+        best.or(last)
+            .ok_or_else(|| "Similarity search did not produce any output".to_string())
+    }
+
+    /// Encodes `img` at the highest quality whose output fits within `target` bytes.
+    ///
+    /// The quality is binary searched between 1 and 100. When even quality 1 is too large and
+    /// `downscale_to_target_size` is set, the image is shrunk by an estimate derived from the
+    /// size overshoot and the search is repeated.
+    ///
+    /// # Arguments
+    ///
+    /// * `img`: The geometry-adjusted image to be encoded.
+    /// * `params`: The compression parameters containing format and downscale info.
+    /// * `exif`: The EXIF bytes to preserve, counted towards the output size.
+    /// * `target`: The maximum output size in bytes.
+    /// * `cancelled`: Flag checked between encode attempts to stop early.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the output bytes, the chosen quality and the final dimensions, or an
+    /// error string when the target size cannot be reached.
+    fn encode_to_target_size(
+        &self,
+        mut img: DynamicImage,
+        params: &CompressionParams,
+        exif: Option<&img_parts::Bytes>,
+        target: u64,
+        cancelled: &AtomicBool,
+    ) -> Result<TargetSizeEncoding, String> {
+        const MAX_DOWNSCALE_STEPS: usize = 8;
+
+        let mut attempt = params.clone();
+        for _ in 0..=MAX_DOWNSCALE_STEPS {
+            let mut best = None;
+            let mut smallest_size = u64::MAX;
+            let (mut low, mut high) = (1u8, 100u8);
+            while low <= high {
+                if cancelled.load(Ordering::Relaxed) {
+                    return Err("COMPRESSION_ABORTED".to_string());
+                }
+
+                attempt.quality = low + (high - low) / 2;
+                let bytes = self.encode_with_exif(&img, &attempt, exif)?;
+                if bytes.len() as u64 <= target {
+                    best = Some((bytes, attempt.quality));
+                    low = attempt.quality + 1;
+                } else {
+                    smallest_size = smallest_size.min(bytes.len() as u64);
+                    high = attempt.quality - 1;
+                }
+            }
+
+            if let Some((bytes, quality)) = best {
+                return Ok((bytes, quality, img.dimensions()));
+            }
+
+            let (w, h) = img.dimensions();
+            if !params.downscale_to_target_size || (w == 1 && h == 1) {
+                break;
+            }
+
+            // Encoded size scales roughly with the pixel count, so shrink both sides by the
+            // square root of the overshoot, with some headroom.
+            let factor = ((target as f64 / smallest_size as f64).sqrt() * 0.9).clamp(0.1, 0.9);
+            let new_w = ((w as f64 * factor) as u32).max(1);
+            let new_h = ((h as f64 * factor) as u32).max(1);
+            img = img.resize(new_w, new_h, image::imageops::FilterType::Lanczos3);
+        }
+
+        Err(format!(
+            "Unable to compress the image below the target size of {target} bytes"
+        ))
     }
 
     /// Reads EXIF bytes from the raw file bytes.
@@ -268,28 +1293,56 @@ impl ImageService {
     /// # Returns
     ///
     /// The transformed image with applied geometry.
-    fn apply_geometry(&self, mut img: DynamicImage, params: &CompressionParams) -> DynamicImage {
-        // Scale takes effect only when no explicit dimensions are set.
-        if params.width.is_none() && params.height.is_none() && params.scale < 100 {
-            let (w, h) = img.dimensions();
-            let new_w = ((w as u64 * params.scale as u64) / 100).max(1) as u32;
-            let new_h = ((h as u64 * params.scale as u64) / 100).max(1) as u32;
-            img = img.resize(new_w, new_h, image::imageops::FilterType::Lanczos3);
+    fn apply_geometry(&self, img: DynamicImage, params: &CompressionParams) -> DynamicImage {
+        let (w, h) = img.dimensions();
+        let (new_w, new_h) = self.output_dimensions(w, h, params);
+        if (new_w, new_h) == (w, h) {
+            img
+        } else {
+            img.resize_exact(new_w, new_h, image::imageops::FilterType::Lanczos3)
+        }
+    }
+
+    /// Computes the dimensions that [`apply_geometry`](Self::apply_geometry) produces for an
+    /// image of the given size.
+    ///
+    /// # Arguments
+    ///
+    /// * `w`: The original width in pixels.
+    /// * `h`: The original height in pixels.
+    /// * `params`: The compression parameters containing scaling and dimension info.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of the output width and height in pixels.
+    fn output_dimensions(&self, w: u32, h: u32, params: &CompressionParams) -> (u32, u32) {
+        if w == 0 || h == 0 {
+            return (w, h);
         }
 
         match (params.width, params.height) {
-            (Some(w), Some(h)) => img.resize(w, h, image::imageops::FilterType::Lanczos3),
-            (Some(w), None) => {
-                let (ow, oh) = img.dimensions();
-                let h = ((oh as f32 * (w as f32 / ow as f32)).round() as u32).max(1);
-                img.resize(w, h, image::imageops::FilterType::Lanczos3)
+            // Both dimensions form a bounding box; the aspect ratio is preserved.
+            (Some(bw), Some(bh)) => {
+                let ratio = (bw as f64 / w as f64).min(bh as f64 / h as f64);
+                (
+                    ((w as f64 * ratio).round() as u32).max(1),
+                    ((h as f64 * ratio).round() as u32).max(1),
+                )
+            }
+            (Some(nw), None) => {
+                let nh = ((h as f32 * (nw as f32 / w as f32)).round() as u32).max(1);
+                (nw, nh)
             }
-            (None, Some(h)) => {
-                let (ow, oh) = img.dimensions();
-                let w = ((ow as f32 * (h as f32 / oh as f32)).round() as u32).max(1);
-                img.resize(w, h, image::imageops::FilterType::Lanczos3)
+            (None, Some(nh)) => {
+                let nw = ((w as f32 * (nh as f32 / h as f32)).round() as u32).max(1);
+                (nw, nh)
             }
-            _ => img,
+            // Scale takes effect only when no explicit dimensions are set.
+            (None, None) if params.scale < 100 => (
+                ((w as u64 * params.scale as u64) / 100).max(1) as u32,
+                ((h as u64 * params.scale as u64) / 100).max(1) as u32,
+            ),
+            (None, None) => (w, h),
         }
     }
 
@@ -319,38 +1372,170 @@ impl ImageService {
 
     /// Resolves the final output file path from the source file path and params.
     ///
+    /// Reads what the path depends on with [`read_source_info`](Self::read_source_info) and
+    /// renders it with [`render_output_path`](Self::render_output_path).
+    ///
     /// # Arguments
     ///
     /// * `file`: The original source file path.
+    /// * `index`: The 0-based position of the file in the batch.
     /// * `params`: The compression parameters containing output path info.
     ///
     /// # Returns
     ///
     /// A string representing the resolved output file path.
-    pub fn resolve_output_path(&self, file: &str, params: &CompressionParams) -> String {
-        if params.is_output_a_directory {
+    pub fn resolve_output_path(
+        &self,
+        file: &str,
+        index: usize,
+        params: &CompressionParams,
+    ) -> String {
+        let info = self.read_source_info(file, Some(params));
+        self.render_output_path(file, index, params, &info)
+    }
+
+    /// Reads what the output path of `file` depends on: its format, dimensions and checksum.
+    ///
+    /// # Arguments
+    ///
+    /// * `file`: The source file path.
+    /// * `params`: The compression parameters, or `None` to read everything. With parameters,
+    ///   only what they need is read; the checksum reads the whole file.
+    ///
+    /// # Returns
+    ///
+    /// The source information; values that were not needed or could not be read are `None`.
+    pub fn read_source_info(&self, file: &str, params: Option<&CompressionParams>) -> SourceInfo {
+        let needs_format = params.is_none_or(|params| {
+            params.format == OutputFormat::Original || params.in_place_backup.is_some()
+        });
+        let needs_token = |token| {
+            params.is_none_or(|params| {
+                params.in_place_backup.is_none()
+                    && params.is_output_a_directory
+                    && params.file_name_template.uses(token)
+            })
+        };
+
+        SourceInfo {
+            format: needs_format
+                .then(|| self.detect_source_format(file))
+                .flatten(),
+            dimensions: (needs_token(Token::Width) || needs_token(Token::Height))
+                .then(|| image::image_dimensions(file).ok())
+                .flatten(),
+            checksum: needs_token(Token::Hash8)
+                .then(|| fs::read(file).ok().map(|bytes| crc32fast::hash(&bytes)))
+                .flatten(),
+        }
+    }
+
+    /// Renders the output file path of `file` without touching the file system.
+    ///
+    /// In place, the output is the source path itself, or the source path with the output
+    /// format's extension when the format changes. In an output directory the file name is
+    /// rendered from `params.file_name_template`; `{quality}` is the configured quality, not a
+    /// searched one, and tokens missing from `info` render as `0`.
+    ///
+    /// # Arguments
+    ///
+    /// * `file`: The original source file path.
+    /// * `index`: The 0-based position of the file in the batch.
+    /// * `params`: The compression parameters containing output path info.
+    /// * `info`: What was read about the source file.
+    ///
+    /// # Returns
+    ///
+    /// A string representing the output file path.
+    pub fn render_output_path(
+        &self,
+        file: &str,
+        index: usize,
+        params: &CompressionParams,
+        info: &SourceInfo,
+    ) -> String {
+        let format = params.resolve_format(info.format);
+
+        if params.in_place_backup.is_some() {
+            // Keep the source's own extension (e.g. `.jpeg` or `.tif`) when the format stays.
+            if info.format == Some(format) {
+                file.to_string()
+            } else {
+                Path::new(file)
+                    .with_extension(format.extension())
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        } else if params.is_output_a_directory {
             let file_stem = Path::new(file)
                 .file_stem()
                 .and_then(|name| name.to_str())
                 .unwrap_or("output");
 
-            let stem = format!("{}_compressed", file_stem);
+            let (width, height) = info
+                .dimensions
+                .map(|(w, h)| self.output_dimensions(w, h, params))
+                .unwrap_or((0, 0));
+            let file_name = params.file_name_template.render(|token| match token {
+                Token::Stem => file_stem.to_string(),
+                Token::Ext => format.extension().to_string(),
+                Token::Format => format.to_string(),
+                Token::Width => width.to_string(),
+                Token::Height => height.to_string(),
+                Token::Quality => params.quality.to_string(),
+                Token::Date => chrono::Local::now().format("%Y-%m-%d").to_string(),
+                Token::Index => (index + 1).to_string(),
+                Token::Hash8 => format!("{:08x}", info.checksum.unwrap_or_default()),
+            });
 
-            Path::new(&*params.output_path)
-                .join(format!("{}.{}", stem, params.format.extension()))
-                .to_string_lossy()
-                .into_owned()
+            let output_dir = Path::new(&*params.output_path);
+            let output_dir = match params
+                .source_root
+                .as_deref()
+                .and_then(|root| Path::new(file).parent()?.strip_prefix(root).ok())
+            {
+                Some(relative_dir) => output_dir.join(relative_dir),
+                None => output_dir.to_path_buf(),
+            };
+
+            output_dir.join(file_name).to_string_lossy().into_owned()
         } else {
             params.output_path.to_string()
         }
     }
 
+    /// Detects the format of `file` from its leading bytes, falling back to its extension.
+    ///
+    /// # Arguments
+    ///
+    /// * `file`: The path of the file to inspect.
+    ///
+    /// # Returns
+    ///
+    /// The detected image format, or `None` if it could not be determined.
+    fn detect_source_format(&self, file: &str) -> Option<OutputFormat> {
+        let mut header = Vec::with_capacity(32);
+        fs::File::open(file)
+            .and_then(|f| f.take(32).read_to_end(&mut header))
+            .ok()
+            .and_then(|_| OutputFormat::detect(&header))
+            .or_else(|| {
+                let extension = Path::new(file).extension()?.to_str()?.to_lowercase();
+                match ImageFormat::from_extension(&extension) {
+                    Some(format) => OutputFormat::from_image_format(format),
+                    None => (extension == OutputFormat::JpegXl.extension())
+                        .then_some(OutputFormat::JpegXl),
+                }
+            })
+    }
+
     /// Resolves output paths for a batch of input files, disambiguating any collisions.
     ///
     /// Each input is mapped through [`resolve_output_path`](Self::resolve_output_path). When two
     /// inputs would resolve to the same output path, subsequent paths gain a numeric suffix
     /// (`name_2.ext`, `name_3.ext`, ...) so no file is silently overwritten by another item in
-    /// the same batch.
+    /// the same batch. In place, sources that keep their own path are resolved first, so a
+    /// converted file never takes the path of another input.
     ///
     /// # Arguments
     ///
@@ -365,35 +1550,27 @@ impl ImageService {
         files: &[String],
         params: &CompressionParams,
     ) -> Vec<String> {
-        let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
+        // In place, every source keeps its own path, whatever order the inputs are in.
+        let mut seen: std::collections::HashSet<String> = if params.in_place_backup.is_some() {
+            files.iter().cloned().collect()
+        } else {
+            std::collections::HashSet::new()
+        };
         files
             .iter()
-            .map(|file| {
-                let candidate = self.resolve_output_path(file, params);
-                if seen.insert(candidate.clone()) {
+            .enumerate()
+            .map(|(index, file)| {
+                let candidate = self.resolve_output_path(file, index, params);
+                if (params.in_place_backup.is_some() && candidate == *file)
+                    || seen.insert(candidate.clone())
+                {
                     return candidate;
                 }
 
-                let path = Path::new(&candidate);
-                let ext = path
-                    .extension()
-                    .and_then(|e| e.to_str())
-                    .unwrap_or_default()
-                    .to_owned();
-                let stem = path
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("output")
-                    .to_owned();
                 let mut n: u32 = 2;
                 loop {
-                    let new_name = if ext.is_empty() {
-                        format!("{}_{}", stem, n)
-                    } else {
-                        format!("{}_{}.{}", stem, n, ext)
-                    };
-                    let disambiguated = path
-                        .with_file_name(&new_name)
+                    let disambiguated = self
+                        .with_numeric_suffix(Path::new(&candidate), n)
                         .to_string_lossy()
                         .into_owned();
                     if seen.insert(disambiguated.clone()) {
@@ -405,20 +1582,244 @@ impl ImageService {
             .collect()
     }
 
+    /// Appends a numeric suffix to the file stem of `path` (`name.ext` becomes `name_n.ext`).
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The path to add the suffix to.
+    /// * `n`: The suffix number.
+    ///
+    /// # Returns
+    ///
+    /// The suffixed path.
+    fn with_numeric_suffix(&self, path: &Path, n: u32) -> PathBuf {
+        let stem = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("output");
+        let new_name = match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if !ext.is_empty() => format!("{}_{}.{}", stem, n, ext),
+            _ => format!("{}_{}", stem, n),
+        };
+        path.with_file_name(new_name)
+    }
+
+    /// Checks whether a file can be recompressed losslessly, i.e. whether lossless JPEG mode is
+    /// on, the source is a JPEG, and the output keeps the source dimensions.
+    ///
+    /// Only the header is read, which also rejects malformed sources before libjpeg sees them.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw`: The source file bytes.
+    /// * `params`: The compression parameters, with the output format resolved.
+    ///
+    /// # Returns
+    ///
+    /// The source dimensions if the file can be recompressed losslessly, `None` otherwise.
+    fn lossless_jpeg_dimensions(
+        &self,
+        raw: &[u8],
+        params: &CompressionParams,
+    ) -> Option<(u32, u32)> {
+        if params.format != OutputFormat::Jpeg
+            || !params.jpeg_options.lossless
+            || image::guess_format(raw).ok() != Some(ImageFormat::Jpeg)
+        {
+            return None;
+        }
+
+        let (w, h) = image::ImageReader::with_format(Cursor::new(raw), ImageFormat::Jpeg)
+            .into_dimensions()
+            .ok()?;
+        (self.output_dimensions(w, h, params) == (w, h)).then_some((w, h))
+    }
+
+    /// Recompresses a JPEG losslessly and, when `exif` is given, injects it into the output.
+    ///
+    /// A JPEG that cannot be recompressed without decoding, e.g. because libjpeg warns about
+    /// corrupt data, is left to the pixel path.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw`: The source JPEG bytes.
+    /// * `params`: The compression parameters containing the JPEG options.
+    /// * `exif`: The EXIF bytes read from the source file, if they should be preserved.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the final output bytes on success, `Ok(None)` if the JPEG must be
+    /// compressed from its pixels instead, or an error string on failure.
+    fn recompress_jpeg(
+        &self,
+        raw: &[u8],
+        params: &CompressionParams,
+        exif: Option<&img_parts::Bytes>,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let recompressed = match jpeg_lossless::recompress(raw, params.jpeg_options.progressive) {
+            Ok(recompressed) => recompressed,
+            Err(e) => {
+                info!("{e}; compressing the decoded pixels instead");
+                return Ok(None);
+            }
+        };
+        match exif {
+            Some(exif) => self.inject_exif(recompressed, exif.clone(), params.format),
+            None => Ok(recompressed),
+        }
+        .map(Some)
+    }
+
+    /// Decodes the frames of an animated source that is written as an animated GIF or WebP,
+    /// dropping frames above the maximum frame rate.
+    ///
+    /// Animations written to other formats keep only their first frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw`: The source file bytes.
+    /// * `params`: The compression parameters, with the output format resolved.
+    ///
+    /// # Returns
+    ///
+    /// The animation, `Ok(None)` if the source is not animated or the output format cannot
+    /// hold an animation, or an error string if the frames cannot be decoded.
+    fn decode_animation(
+        &self,
+        raw: &[u8],
+        params: &CompressionParams,
+    ) -> Result<Option<Animation>, String> {
+        if !matches!(params.format, OutputFormat::Gif | OutputFormat::WebP) {
+            return Ok(None);
+        }
+
+        let mut animation = Animation::decode(raw)?;
+        if let (Some(animation), Some(max_frame_rate)) = (&mut animation, params.max_frame_rate) {
+            animation.limit_frame_rate(max_frame_rate);
+        }
+        Ok(animation)
+    }
+
+    /// Encodes an animation in the output format and, when `exif` is given, injects it into
+    /// the output.
+    ///
+    /// Quality is only used for WebP; target size and similarity searches do not apply to
+    /// animations.
+    ///
+    /// # Arguments
+    ///
+    /// * `animation`: The geometry-adjusted animation.
+    /// * `params`: The compression parameters, with an output format of GIF or WebP.
+    /// * `exif`: The EXIF bytes read from the source file, if they should be preserved.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the final output bytes on success, or an error string on failure.
+    fn encode_animation(
+        &self,
+        animation: &Animation,
+        params: &CompressionParams,
+        exif: Option<&img_parts::Bytes>,
+    ) -> Result<Vec<u8>, String> {
+        let encoded = match params.format {
+            OutputFormat::Gif => animation.encode_gif(params.optimize_gif_palette)?,
+            OutputFormat::WebP => animation.encode_webp(params.quality)?,
+            format => return Err(format!("{format} cannot hold an animation")),
+        };
+        match exif {
+            Some(exif) => self.inject_exif(encoded, exif.clone(), params.format),
+            None => Ok(encoded),
+        }
+    }
+
+    /// Encodes `img` as a JPEG with mozjpeg, applying the advanced JPEG options.
+    ///
+    /// mozjpeg reports errors by unwinding, so the encoder runs inside `catch_unwind`.
+    ///
+    /// # Arguments
+    ///
+    /// * `img`: The image to be encoded. Alpha is discarded.
+    /// * `params`: The compression parameters containing the quality and JPEG options.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the JPEG bytes on success, or an error string on failure.
+    fn encode_mozjpeg(
+        &self,
+        img: &DynamicImage,
+        params: &CompressionParams,
+    ) -> Result<Vec<u8>, String> {
+        let options = params.jpeg_options;
+        let (pixels, color_space) = if img.color().has_color() {
+            (img.to_rgb8().into_raw(), mozjpeg::ColorSpace::JCS_RGB)
+        } else {
+            (
+                img.to_luma8().into_raw(),
+                mozjpeg::ColorSpace::JCS_GRAYSCALE,
+            )
+        };
+
+        let encode = || -> std::io::Result<Vec<u8>> {
+            // mozjpeg defaults to its maximum compression profile, which enables trellis
+            // quantization and progressive scans; the libjpeg defaults disable both.
+            let mut compress = mozjpeg::Compress::new(color_space);
+            if !options.trellis {
+                compress.set_fastest_defaults();
+            }
+            compress.set_size(img.width() as usize, img.height() as usize);
+            compress.set_quality(params.quality as f32);
+            let chroma = options.chroma_subsampling.pixel_size();
+            compress.set_chroma_sampling_pixel_sizes(chroma, chroma);
+            if options.progressive {
+                compress.set_progressive_mode();
+            } else {
+                compress.set_optimize_scans(false);
+            }
+            compress.set_optimize_coding(options.optimize_huffman);
+
+            let mut started = compress.start_compress(Vec::new())?;
+            started.write_scanlines(&pixels)?;
+            started.finish()
+        };
+
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(encode)) {
+            Ok(result) => result.map_err(|e| format!("Failed to encode JPEG: {e}")),
+            Err(_) => Err("Failed to encode JPEG: mozjpeg reported an error".to_string()),
+        }
+    }
+
     /// Encodes `img` into a heap-allocated byte buffer in the requested format.
     ///
     /// # Arguments
     ///
     /// * `img`: The image to be encoded.
     /// * `params`: The compression parameters containing format and quality info.
+    /// * `exif`: EXIF bytes for formats whose container is written by the encoder itself (AVIF, JPEG XL).
     ///
     /// # Returns
     ///
     /// A `Result` containing the encoded image bytes on success, or an error string on failure.
-    fn encode(&self, img: &DynamicImage, params: &CompressionParams) -> Result<Vec<u8>, String> {
+    fn encode(
+        &self,
+        img: &DynamicImage,
+        params: &CompressionParams,
+        exif: Option<&[u8]>,
+    ) -> Result<Vec<u8>, String> {
         let mut cursor = Cursor::new(Vec::new());
 
         match params.format {
+            OutputFormat::Original => {
+                return Err("Output format was not resolved for this file".to_string());
+            }
+            OutputFormat::Jpeg
+                if JpegOptions {
+                    lossless: false,
+                    ..params.jpeg_options
+                } != JpegOptions::default() =>
+            {
+                let encoded = self.encode_mozjpeg(img, params)?;
+                cursor.get_mut().extend_from_slice(&encoded);
+            }
             OutputFormat::Jpeg => {
                 let mut encoder =
                     image::codecs::jpeg::JpegEncoder::new_with_quality(&mut cursor, params.quality);
@@ -438,9 +1839,24 @@ impl ImageService {
 
                 cursor.get_mut().extend_from_slice(&webp_memory);
             }
+            OutputFormat::Png if params.lossy_png && params.quality < 100 => {
+                let quantized = self.quantize_png(img, params)?;
+                cursor.get_mut().extend_from_slice(&quantized);
+            }
             OutputFormat::Png => {
-                img.write_to(&mut cursor, ImageFormat::Png)
+                // The optimizer recompresses the image data anyway, so the initial encode
+                // only needs to be fast.
+                let mut unoptimized = Cursor::new(Vec::new());
+                let encoder = image::codecs::png::PngEncoder::new_with_quality(
+                    &mut unoptimized,
+                    image::codecs::png::CompressionType::Fast,
+                    image::codecs::png::FilterType::NoFilter,
+                );
+                img.write_with_encoder(encoder)
                     .map_err(|e| format!("Failed to encode PNG: {e}"))?;
+
+                let optimized = self.optimize_png(unoptimized.get_ref(), params)?;
+                cursor.get_mut().extend_from_slice(&optimized);
             }
             OutputFormat::Gif => {
                 img.write_to(&mut cursor, ImageFormat::Gif)
@@ -460,15 +1876,147 @@ impl ImageService {
                     .write_image(&bytes, img.width(), img.height(), color_type)
                     .map_err(|e| format!("Failed to write TIFF: {e}"))?;
             }
+            OutputFormat::Avif => {
+                let pixels: Vec<ravif::RGBA8> = img
+                    .to_rgba8()
+                    .pixels()
+                    .map(|p| ravif::RGBA8::new(p[0], p[1], p[2], p[3]))
+                    .collect();
+
+                let mut encoder = ravif::Encoder::new()
+                    .with_quality(params.quality as f32)
+                    .with_alpha_quality(params.quality as f32)
+                    .with_speed(params.avif_speed);
+                if let Some(exif) = exif {
+                    encoder = encoder.with_exif(exif);
+                }
+
+                let encoded = encoder
+                    .encode_rgba(ravif::Img::new(
+                        &pixels[..],
+                        img.width() as usize,
+                        img.height() as usize,
+                    ))
+                    .map_err(|e| format!("Failed to encode AVIF: {e}"))?;
+
+                cursor.get_mut().extend_from_slice(&encoded.avif_file);
+            }
+            OutputFormat::JpegXl => {
+                let (bytes, color_type) = self.pixel_data_slice(img);
+                let encoded = jpeg_xl::encode(&bytes, img.width(), img.height(), color_type, exif)?;
+                cursor.get_mut().extend_from_slice(&encoded);
+            }
         }
 
         Ok(cursor.into_inner())
     }
 
+    /// Losslessly optimizes an encoded PNG buffer.
+    ///
+    /// Runs the oxipng preset matching `png_optimization_level`, which searches filter
+    /// strategies, reduces bit depth, color type and palette where possible, and recompresses
+    /// the image data. Non-critical metadata chunks are stripped; EXIF is re-injected
+    /// afterwards when requested.
+    ///
+    /// # Arguments
+    ///
+    /// * `png`: The encoded PNG bytes to optimize.
+    /// * `params`: The compression parameters containing the PNG optimization settings.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the optimized PNG bytes on success, or an error string on failure.
+    fn optimize_png(&self, png: &[u8], params: &CompressionParams) -> Result<Vec<u8>, String> {
+        oxipng::optimize_from_memory(png, &self.png_options(params))
+            .map_err(|e| format!("Failed to optimize PNG: {e}"))
+    }
+
+    /// Builds the oxipng options for the configured optimization level and deflater.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: The compression parameters containing the PNG optimization settings.
+    ///
+    /// # Returns
+    ///
+    /// The oxipng options to optimize PNG output with.
+    fn png_options(&self, params: &CompressionParams) -> oxipng::Options {
+        let mut options = oxipng::Options::from_preset(params.png_optimization_level.min(6));
+        options.strip = oxipng::StripChunks::Safe;
+        if params.png_zopfli {
+            options.deflater = oxipng::Deflater::Zopfli(oxipng::ZopfliOptions::default());
+        }
+        options
+    }
+
+    /// Encodes `img` as a palette-based PNG, trading color accuracy for a much smaller file.
+    ///
+    /// The quality setting controls both the maximum palette size (from 2 colors at quality 1
+    /// up to 256 colors at quality 99) and the dithering level. The indexed image is then
+    /// written through the same optimizer as lossless PNG output.
+    ///
+    /// # Arguments
+    ///
+    /// * `img`: The image to be quantized.
+    /// * `params`: The compression parameters containing quality and PNG optimization info.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the encoded PNG bytes on success, or an error string on failure.
+    fn quantize_png(
+        &self,
+        img: &DynamicImage,
+        params: &CompressionParams,
+    ) -> Result<Vec<u8>, String> {
+        let quality = params.quality.clamp(1, 99) as u32;
+        let max_colors = 2 + (254 * (quality - 1)) / 98;
+        let dithering = quality as f32 / 100.0;
+
+        let pixels: Vec<imagequant::RGBA> = img
+            .to_rgba8()
+            .pixels()
+            .map(|p| imagequant::RGBA::new(p[0], p[1], p[2], p[3]))
+            .collect();
+
+        let mut liq = imagequant::new();
+        liq.set_max_colors(max_colors)
+            .map_err(|e| format!("Failed to configure PNG quantizer: {e}"))?;
+        let mut image = liq
+            .new_image_borrowed(&pixels, img.width() as usize, img.height() as usize, 0.0)
+            .map_err(|e| format!("Failed to prepare PNG quantization: {e}"))?;
+        let mut quantized = liq
+            .quantize(&mut image)
+            .map_err(|e| format!("Failed to quantize PNG: {e}"))?;
+        quantized
+            .set_dithering_level(dithering)
+            .map_err(|e| format!("Failed to quantize PNG: {e}"))?;
+        let (palette, indexes) = quantized
+            .remapped(&mut image)
+            .map_err(|e| format!("Failed to quantize PNG: {e}"))?;
+
+        let palette = palette
+            .into_iter()
+            .map(|c| oxipng::RGBA8::new(c.r, c.g, c.b, c.a))
+            .collect();
+        let raw = oxipng::RawImage::new(
+            img.width(),
+            img.height(),
+            oxipng::ColorType::Indexed { palette },
+            oxipng::BitDepth::Eight,
+            indexes,
+        )
+        .map_err(|e| format!("Failed to encode PNG: {e}"))?;
+
+        raw.create_optimized_png(&self.png_options(params))
+            .map_err(|e| format!("Failed to optimize PNG: {e}"))
+    }
+
     /// Re-injects EXIF bytes into an already-encoded in-memory image buffer.
     ///
     /// GIF, BMP, and TIFF do not support EXIF via `img-parts`; for those the
-    /// original `bytes` are returned unchanged without an extra copy.
+    /// original `bytes` are returned unchanged without an extra copy. The AVIF
+    /// container is serialized by the encoder, which already embedded the EXIF
+    /// passed to [`encode`](Self::encode), so AVIF bytes are returned unchanged too.
     ///
     /// # Arguments
     ///
@@ -505,9 +2053,316 @@ impl ImageService {
         match format {
             // GIF/BMP/TIFF cannot carry EXIF via img-parts; return bytes untouched.
             OutputFormat::Gif | OutputFormat::Bmp | OutputFormat::Tiff => Ok(bytes),
+            // ravif and the JPEG XL encoder write EXIF while building the container.
+            OutputFormat::Avif | OutputFormat::JpegXl => Ok(bytes),
+            // `encode` never produces output for an unresolved format.
+            OutputFormat::Original => Ok(bytes),
             OutputFormat::Jpeg => inject!(img_parts::jpeg::Jpeg, "JPEG", bytes.into()),
             OutputFormat::Png => inject!(img_parts::png::Png, "PNG", bytes.into()),
             OutputFormat::WebP => inject!(img_parts::webp::WebP, "WebP", bytes.into()),
         }
     }
 }
+
+/// Reads the dimensions an AVIF file declares in the image spatial extents (`ispe`) property
+/// of its container, after checking that it holds image data.
+///
+/// # Arguments
+///
+/// * `bytes`: The AVIF file.
+///
+/// # Returns
+///
+/// The width and height, or `None` if the container is malformed or holds no image data.
+fn avif_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let ftyp = find_box(bytes, b"ftyp")?;
+    if ftyp.get(..4)? != b"avif" || find_box(bytes, b"mdat")?.is_empty() {
+        return None;
+    }
+    // `meta` is a full box, whose payload starts with its version and flags.
+    let meta = find_box(bytes, b"meta")?;
+    let ipco = find_box(find_box(meta.get(4..)?, b"iprp")?, b"ipco")?;
+    let ispe = find_box(ipco, b"ispe")?;
+    let width = u32::from_be_bytes(ispe.get(4..8)?.try_into().ok()?);
+    let height = u32::from_be_bytes(ispe.get(8..12)?.try_into().ok()?);
+    Some((width, height))
+}
+
+/// Finds the first ISO BMFF box of type `box_type` in a sequence of boxes.
+///
+/// # Arguments
+///
+/// * `bytes`: The boxes to search.
+/// * `box_type`: The four-character box type.
+///
+/// # Returns
+///
+/// The payload of the box, or `None` if it is missing or a box before it is malformed.
+fn find_box<'a>(mut bytes: &'a [u8], box_type: &[u8; 4]) -> Option<&'a [u8]> {
+    while bytes.len() >= 8 {
+        let (header, size) = match u32::from_be_bytes(bytes[..4].try_into().ok()?) {
+            // A box extending to the end of the data.
+            0 => (8, bytes.len()),
+            // A 64-bit size follows the type.
+            1 => (
+                16,
+                usize::try_from(u64::from_be_bytes(bytes.get(8..16)?.try_into().ok()?)).ok()?,
+            ),
+            size => (8, size as usize),
+        };
+        if size < header || size > bytes.len() {
+            return None;
+        }
+        if &bytes[4..8] == box_type {
+            return Some(&bytes[header..size]);
+        }
+        bytes = &bytes[size..];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an empty scratch folder unique to the calling test.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "compressr-image-service-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes a small image in the format given by the extension of `path`.
+    fn write_image(path: &Path) -> String {
+        image::RgbImage::from_fn(8, 8, |x, y| image::Rgb([x as u8 * 30, y as u8 * 30, 90]))
+            .save(path)
+            .unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Returns parameters that compress in place into `format`, backing up into `backup`.
+    fn in_place_params(format: OutputFormat, backup: BackupTarget) -> CompressionParams {
+        CompressionParams {
+            output_path: Arc::from(""),
+            is_output_a_directory: false,
+            scale: 100,
+            width: None,
+            height: None,
+            quality: 80,
+            lossy_png: false,
+            avif_speed: 6,
+            png_optimization_level: 2,
+            png_zopfli: false,
+            max_frame_rate: None,
+            optimize_gif_palette: false,
+            target_size: None,
+            downscale_to_target_size: false,
+            min_similarity: None,
+            larger_output_policy: LargerOutputPolicy::WriteAnyway,
+            existing_file_policy: ExistingFilePolicy::Overwrite,
+            format_mapping: Arc::new(OutputFormat::default_mapping()),
+            source_root: None,
+            file_name_template: Arc::new(FileNameTemplate::default()),
+            in_place_backup: Some(backup),
+            jpeg_options: JpegOptions::default(),
+            format,
+            preserve_exif: false,
+            output_path_override: None,
+        }
+    }
+
+    /// Returns a photo-like image with gradients and fine detail.
+    fn photo() -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 48, |x, y| {
+            image::Rgb([
+                (x * 4) as u8,
+                (y * 5) as u8,
+                ((x * y) % 97 + (x ^ y) % 61) as u8,
+            ])
+        }))
+    }
+
+    /// Returns parameters that write `format` into an output directory.
+    fn output_params(format: OutputFormat) -> CompressionParams {
+        CompressionParams {
+            output_path: Arc::from("out"),
+            is_output_a_directory: true,
+            in_place_backup: None,
+            ..in_place_params(format, BackupTarget::Sibling)
+        }
+    }
+
+    /// Encodes `img` with `params` at `quality` and returns its similarity to `img`.
+    fn similarity_at(img: &DynamicImage, params: &CompressionParams, quality: u8) -> f64 {
+        let bytes = ImageService::new()
+            .encode_with_exif(
+                img,
+                &CompressionParams {
+                    quality,
+                    ..params.clone()
+                },
+                None,
+            )
+            .unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        similarity::ssim(&img.to_luma8(), &decoded.to_luma8())
+    }
+
+    #[test]
+    fn similarity_search_finds_the_lowest_quality_that_meets_the_target() {
+        let img = photo();
+        let params = output_params(OutputFormat::Jpeg);
+        let (_, quality, score) = ImageService::new()
+            .encode_to_min_similarity(&img, &params, None, 0.9, &AtomicBool::new(false))
+            .unwrap();
+
+        assert!(score >= 0.9);
+        assert!(quality > 1 && quality < 100);
+        assert_eq!(similarity_at(&img, &params, quality), score);
+        assert!(similarity_at(&img, &params, quality - 1) < 0.9);
+    }
+
+    #[test]
+    fn unreachable_similarity_falls_back_to_full_quality() {
+        let img = photo();
+        let params = output_params(OutputFormat::Jpeg);
+        let (_, quality, score) = ImageService::new()
+            .encode_to_min_similarity(&img, &params, None, 1.0, &AtomicBool::new(false))
+            .unwrap();
+
+        assert_eq!(quality, 100);
+        assert!(score < 1.0);
+    }
+
+    #[test]
+    fn similarity_search_stops_when_cancelled() {
+        let result = ImageService::new().encode_to_min_similarity(
+            &photo(),
+            &output_params(OutputFormat::WebP),
+            None,
+            0.9,
+            &AtomicBool::new(true),
+        );
+        assert_eq!(result.unwrap_err(), "COMPRESSION_ABORTED");
+    }
+
+    #[test]
+    fn similarity_is_not_searched_for_avif() {
+        let mapping = OutputFormat::default_mapping();
+        assert!(!OutputFormat::Avif.supports_similarity(true, &mapping));
+        assert!(OutputFormat::Jpeg.supports_similarity(false, &mapping));
+        assert!(OutputFormat::Original.supports_similarity(false, &mapping));
+        let mut to_avif = mapping.clone();
+        to_avif.insert(OutputFormat::Png, OutputFormat::Avif);
+        assert!(!OutputFormat::Original.supports_similarity(false, &to_avif));
+
+        let result = ImageService::new().encode_to_min_similarity(
+            &photo(),
+            &output_params(OutputFormat::Avif),
+            None,
+            0.9,
+            &AtomicBool::new(false),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn avif_outputs_are_verified_through_their_container() {
+        let dir = scratch_dir("avif");
+        let source = write_image(&dir.join("a.png"));
+        let params = CompressionParams {
+            output_path: Arc::from(dir.to_string_lossy().as_ref()),
+            avif_speed: 10,
+            ..output_params(OutputFormat::Avif)
+        };
+
+        let result = ImageService::new()
+            .compress_single(source, &params, Arc::new(AtomicBool::new(false)))
+            .unwrap();
+        assert_eq!(result.action, OutputAction::Written);
+        assert!(result.can_remove_original());
+        let written = fs::read(result.output_path.unwrap()).unwrap();
+        assert_eq!(avif_dimensions(&written), Some((8, 8)));
+
+        // Truncated image data or a broken container fails verification.
+        assert_eq!(avif_dimensions(&written[..written.len() / 2]), None);
+        let mut wrong_brand = written.clone();
+        wrong_brand[8..12].copy_from_slice(b"heic");
+        assert_eq!(avif_dimensions(&wrong_brand), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn in_place_sources_sharing_a_target_get_distinct_paths() {
+        let dir = scratch_dir("shared-target");
+        let files = [
+            write_image(&dir.join("a.png")),
+            write_image(&dir.join("a.jpg")),
+        ];
+        let params = in_place_params(OutputFormat::WebP, BackupTarget::Sibling);
+
+        let paths = ImageService::new().resolve_unique_output_paths(&files, &params);
+        assert_eq!(
+            paths,
+            [dir.join("a.webp"), dir.join("a_2.webp")].map(|p| p.to_string_lossy().into_owned())
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn in_place_sources_keep_their_own_path() {
+        let dir = scratch_dir("own-path");
+        // The converted PNG comes first but must not take the path of the WebP input.
+        let files = [
+            write_image(&dir.join("a.png")),
+            write_image(&dir.join("a.webp")),
+            write_image(&dir.join("b.webp")),
+        ];
+        let params = in_place_params(OutputFormat::WebP, BackupTarget::Sibling);
+
+        let paths = ImageService::new().resolve_unique_output_paths(&files, &params);
+        assert_eq!(
+            paths,
+            [
+                dir.join("a_2.webp").to_string_lossy().into_owned(),
+                files[1].clone(),
+                files[2].clone(),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_backup_leaves_no_output_behind() {
+        let dir = scratch_dir("failed-backup");
+        let source = write_image(&dir.join("b.png"));
+        let original = fs::read(&source).unwrap();
+        fs::write(dir.join("notadir"), []).unwrap();
+        let params = in_place_params(
+            OutputFormat::WebP,
+            BackupTarget::Directory(Arc::from(dir.join("notadir/sub"))),
+        );
+
+        let result = ImageService::new().compress_single(
+            source.clone(),
+            &params,
+            Arc::new(AtomicBool::new(false)),
+        );
+        assert!(
+            result
+                .unwrap_err()
+                .starts_with("Failed to create backup folder")
+        );
+        assert!(!dir.join("b.webp").exists());
+        assert_eq!(fs::read(&source).unwrap(), original);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
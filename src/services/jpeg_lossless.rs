@@ -0,0 +1,194 @@
+use mozjpeg_sys as ffi;
+use std::ffi::{CStr, c_ulong};
+use std::mem;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+/// The APP2 marker, which carries ICC color profiles.
+const ICC_MARKER: c_int = ffi::jpeg_marker::APP0 as c_int + 2;
+
+/// Owns the decompressor, compressor and error manager of a transcode, so libjpeg memory is
+/// released even when an error unwinds out of the transcode.
+struct Transcoder {
+    decompress: ffi::jpeg_decompress_struct,
+    compress: ffi::jpeg_compress_struct,
+    error: ffi::jpeg_error_mgr,
+    output: *mut u8,
+    output_size: c_ulong,
+}
+
+impl Drop for Transcoder {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::jpeg_destroy_compress(&mut self.compress);
+            ffi::jpeg_destroy_decompress(&mut self.decompress);
+            if !self.output.is_null() {
+                libc::free(self.output.cast());
+            }
+        }
+    }
+}
+
+/// Formats the current libjpeg message the way libjpeg's own `format_message` does.
+///
+/// The binding of `format_message` declares its output buffer as shared, so the message is
+/// looked up in the message table and formatted here instead.
+///
+/// # Arguments
+///
+/// * `err` - The error manager holding the message code and parameters.
+///
+/// # Returns
+///
+/// The formatted message.
+fn libjpeg_message(err: &ffi::jpeg_error_mgr) -> String {
+    let code = err.msg_code;
+    if err.jpeg_message_table.is_null() || code <= 0 || code > err.last_jpeg_message {
+        return format!("libjpeg error {code}");
+    }
+
+    let parameters = &err.msg_parm._bindgen_data_;
+    let mut buffer: [c_char; 80] = [0; 80];
+    unsafe {
+        let format = *err.jpeg_message_table.add(code as usize);
+        if format.is_null() {
+            return format!("libjpeg error {code}");
+        }
+        if CStr::from_ptr(format)
+            .to_bytes()
+            .windows(2)
+            .any(|w| w == b"%s")
+        {
+            libc::snprintf(
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                format,
+                parameters.as_ptr().cast::<c_char>(),
+            );
+        } else {
+            let i = parameters.map(|p| p as c_int);
+            libc::snprintf(
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                format,
+                i[0],
+                i[1],
+                i[2],
+                i[3],
+                i[4],
+                i[5],
+                i[6],
+                i[7],
+            );
+        }
+        CStr::from_ptr(buffer.as_ptr())
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Reports a fatal libjpeg error by unwinding with its message, like the `mozjpeg` crate does.
+extern "C-unwind" fn unwind_error_exit(cinfo: &mut ffi::jpeg_common_struct) {
+    let message = match unsafe { cinfo.err.as_ref() } {
+        Some(err) => libjpeg_message(err),
+        None => "unknown libjpeg error".to_string(),
+    };
+    std::panic::resume_unwind(Box::new(message));
+}
+
+/// Counts libjpeg warnings instead of printing them, keeping the first one in the
+/// `Option<String>` that `client_data` points to. Trace messages are ignored.
+extern "C-unwind" fn record_warning(cinfo: &mut ffi::jpeg_common_struct, level: c_int) {
+    if level >= 0 {
+        return;
+    }
+    let Some(err) = (unsafe { cinfo.err.as_mut() }) else {
+        return;
+    };
+    if err.num_warnings == 0
+        && let Some(warning) = unsafe { cinfo.client_data.cast::<Option<String>>().as_mut() }
+    {
+        *warning = Some(libjpeg_message(err));
+    }
+    err.num_warnings += 1;
+}
+
+/// Recompresses a JPEG without decoding it to pixels.
+///
+/// The quantized DCT coefficients are copied as they are, so the image is unchanged, while the
+/// Huffman tables are optimized for the image and the scans are optionally made progressive,
+/// like `jpegtran -optimize`. ICC profiles are kept; every other marker segment, including
+/// EXIF, is stripped.
+///
+/// A source that libjpeg warns about, e.g. because it is truncated or has corrupt data, is
+/// rejected, since copying its coefficients would keep the damage.
+///
+/// # Arguments
+///
+/// * `jpeg` - The source JPEG file.
+/// * `progressive` - Whether to write progressive scans.
+///
+/// # Returns
+///
+/// The recompressed JPEG, or an error message if the source cannot be transcoded.
+pub fn recompress(jpeg: &[u8], progressive: bool) -> Result<Vec<u8>, String> {
+    let mut warning: Option<String> = None;
+    let client_data: *mut Option<String> = &mut warning;
+    let transcode = || unsafe {
+        let mut transcoder: Box<Transcoder> = Box::new(mem::zeroed());
+        ffi::jpeg_std_error(&mut transcoder.error);
+        transcoder.error.error_exit = Some(unwind_error_exit);
+        transcoder.error.emit_message = Some(record_warning);
+        let error: *mut ffi::jpeg_error_mgr = &mut transcoder.error;
+
+        let t = &mut *transcoder;
+        t.decompress.common.err = error;
+        ffi::jpeg_create_decompress(&mut t.decompress);
+        t.decompress.common.client_data = client_data.cast();
+        t.compress.common.err = error;
+        ffi::jpeg_create_compress(&mut t.compress);
+        t.compress.common.client_data = client_data.cast();
+
+        ffi::jpeg_mem_src(&mut t.decompress, jpeg.as_ptr(), jpeg.len() as c_ulong);
+        ffi::jpeg_save_markers(&mut t.decompress, ICC_MARKER, 0xFFFF);
+        ffi::jpeg_read_header(&mut t.decompress, 1);
+        let coefficients = ffi::jpeg_read_coefficients(&mut t.decompress);
+
+        // Copying the parameters also applies mozjpeg's defaults, which include progressive
+        // scans; clearing the scan script writes a single sequential scan instead.
+        ffi::jpeg_copy_critical_parameters(&t.decompress, &mut t.compress);
+        if !progressive {
+            t.compress.scan_info = ptr::null();
+            t.compress.num_scans = 0;
+        }
+        t.compress.optimize_coding = 1;
+
+        ffi::jpeg_mem_dest(&mut t.compress, &mut t.output, &mut t.output_size);
+        ffi::jpeg_write_coefficients(&mut t.compress, coefficients);
+
+        let mut marker = t.decompress.marker_list;
+        while let Some(m) = marker.as_ref() {
+            if c_int::from(m.marker) == ICC_MARKER {
+                ffi::jpeg_write_marker(&mut t.compress, ICC_MARKER, m.data, m.data_length);
+            }
+            marker = m.next;
+        }
+
+        ffi::jpeg_finish_compress(&mut t.compress);
+        ffi::jpeg_finish_decompress(&mut t.decompress);
+
+        if let Some(warning) = (*client_data).take() {
+            return Err(warning);
+        }
+        Ok(std::slice::from_raw_parts(t.output, t.output_size as usize).to_vec())
+    };
+
+    std::panic::catch_unwind(transcode)
+        .unwrap_or_else(|e| {
+            Err(e
+                .downcast_ref::<String>()
+                .cloned()
+                .unwrap_or_else(|| "unknown error".to_string()))
+        })
+        .map_err(|reason| format!("Failed to recompress JPEG losslessly: {reason}"))
+}
@@ -0,0 +1,423 @@
+use image::ExtendedColorType;
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_core::options::EncoderOptions;
+
+/// The signature of a bare JPEG XL codestream.
+const CODESTREAM_SIGNATURE: &[u8] = &[0xFF, 0x0A];
+/// The signature box that starts a JPEG XL container.
+const CONTAINER_SIGNATURE: &[u8] = &[
+    0x00, 0x00, 0x00, 0x0C, b'J', b'X', b'L', b' ', 0x0D, 0x0A, 0x87, 0x0A,
+];
+/// The file type box that follows the container signature.
+const FILE_TYPE_BOX: &[u8] = &[
+    0x00, 0x00, 0x00, 0x14, b'f', b't', b'y', b'p', b'j', b'x', b'l', b' ', 0x00, 0x00, 0x00, 0x00,
+    b'j', b'x', b'l', b' ',
+];
+
+/// Returns whether `bytes` start with a JPEG XL codestream or container signature.
+///
+/// `image::guess_format` does not know JPEG XL, so sources are checked with this first.
+///
+/// # Arguments
+///
+/// * `bytes` - The leading bytes of the file.
+///
+/// # Returns
+///
+/// `true` if the bytes look like a JPEG XL image, `false` otherwise.
+pub fn is_jpeg_xl(bytes: &[u8]) -> bool {
+    bytes.starts_with(CODESTREAM_SIGNATURE) || bytes.starts_with(CONTAINER_SIGNATURE)
+}
+
+/// Encodes 8-bit pixels as lossless JPEG XL.
+///
+/// Uses zune-jpegxl, a port of libjxl's fast lossless encoder. There is no lossy JPEG XL
+/// encoder, so the quality setting does not apply. The encoder needs at least 2x2 pixels, so
+/// a narrower or shorter image is encoded with its edge repeated and the headers are rewritten
+/// to crop the frame back to the image; see [`crop_codestream`].
+///
+/// # Arguments
+///
+/// * `pixels` - The interleaved pixel data.
+/// * `width` - The image width in pixels.
+/// * `height` - The image height in pixels.
+/// * `color` - The layout of `pixels`; one of `L8`, `La8`, `Rgb8` or `Rgba8`.
+/// * `exif` - EXIF bytes to store in the file, if any.
+///
+/// # Returns
+///
+/// The JPEG XL bytes, or an error message on failure.
+pub fn encode(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    color: ExtendedColorType,
+    exif: Option<&[u8]>,
+) -> Result<Vec<u8>, String> {
+    let color_space = match color {
+        ExtendedColorType::L8 => ColorSpace::Luma,
+        ExtendedColorType::La8 => ColorSpace::LumaA,
+        ExtendedColorType::Rgb8 => ColorSpace::RGB,
+        ExtendedColorType::Rgba8 => ColorSpace::RGBA,
+        other => {
+            return Err(format!(
+                "Failed to encode JPEG XL: unsupported pixels {other:?}"
+            ));
+        }
+    };
+    if width == 0 || height == 0 {
+        return Err("Failed to encode JPEG XL: the image is empty".to_string());
+    }
+
+    let (padded_width, padded_height) = (width.max(2), height.max(2));
+    let padded;
+    let pixels = if (padded_width, padded_height) == (width, height) {
+        pixels
+    } else {
+        padded = pad_to(
+            pixels,
+            (width, height),
+            (padded_width, padded_height),
+            color_space.num_components(),
+        );
+        &padded
+    };
+
+    let options = EncoderOptions::new(
+        padded_width as usize,
+        padded_height as usize,
+        color_space,
+        BitDepth::Eight,
+    );
+    let mut codestream = Vec::new();
+    zune_jpegxl::JxlSimpleEncoder::new(pixels, options)
+        .encode(&mut codestream)
+        .map_err(|e| format!("Failed to encode JPEG XL: {e}"))?;
+    if (padded_width, padded_height) != (width, height) {
+        codestream = crop_codestream(
+            &codestream,
+            (width, height),
+            (padded_width, padded_height),
+            color_space,
+        )?;
+    }
+    Ok(match exif {
+        Some(exif) => wrap_in_container(&codestream, exif),
+        None => codestream,
+    })
+}
+
+/// Wraps a bare codestream in a JPEG XL container so that EXIF can be stored next to it.
+///
+/// # Arguments
+///
+/// * `codestream` - The encoded JPEG XL codestream.
+/// * `exif` - The EXIF bytes, starting with the TIFF header.
+///
+/// # Returns
+///
+/// The container bytes.
+fn wrap_in_container(codestream: &[u8], exif: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(
+        CONTAINER_SIGNATURE.len() + FILE_TYPE_BOX.len() + 20 + codestream.len() + exif.len(),
+    );
+    out.extend_from_slice(CONTAINER_SIGNATURE);
+    out.extend_from_slice(FILE_TYPE_BOX);
+    write_box(&mut out, b"jxlc", &[codestream]);
+    // The Exif box starts with the offset of the TIFF header within the payload.
+    write_box(&mut out, b"Exif", &[&0u32.to_be_bytes(), exif]);
+    out
+}
+
+/// Appends an ISO BMFF box made of `parts` to `out`.
+///
+/// # Arguments
+///
+/// * `out` - The buffer to append to.
+/// * `box_type` - The four-character box type.
+/// * `parts` - The box payload, in order.
+fn write_box(out: &mut Vec<u8>, box_type: &[u8; 4], parts: &[&[u8]]) {
+    let size = 8 + parts.iter().map(|part| part.len()).sum::<usize>();
+    out.extend_from_slice(&(size as u32).to_be_bytes());
+    out.extend_from_slice(box_type);
+    for part in parts {
+        out.extend_from_slice(part);
+    }
+}
+
+/// Grows an image by repeating its last column and row.
+///
+/// # Arguments
+///
+/// * `pixels` - The interleaved pixel data.
+/// * `size` - The width and height of `pixels`.
+/// * `padded` - The width and height to grow to; neither may be smaller than `size`.
+/// * `channels` - The number of bytes per pixel.
+///
+/// # Returns
+///
+/// The interleaved pixel data of the grown image.
+fn pad_to(pixels: &[u8], size: (u32, u32), padded: (u32, u32), channels: usize) -> Vec<u8> {
+    let (width, height) = (size.0 as usize, size.1 as usize);
+    let mut out = Vec::with_capacity(padded.0 as usize * padded.1 as usize * channels);
+    for y in 0..padded.1 as usize {
+        let row = &pixels[y.min(height - 1) * width * channels..][..width * channels];
+        for x in 0..padded.0 as usize {
+            out.extend_from_slice(&row[x.min(width - 1) * channels..][..channels]);
+        }
+    }
+    out
+}
+
+/// Rewrites the headers of a codestream encoded from a padded image, so it decodes to the
+/// original size.
+///
+/// zune-jpegxl writes fixed, byte-aligned image and frame headers. They are written again
+/// here with the original image size and a frame that keeps its padded size; JPEG XL crops
+/// a frame reaching past the image. Everything from the table of contents on is kept as is.
+///
+/// # Arguments
+///
+/// * `codestream` - The codestream of the padded image.
+/// * `size` - The original width and height.
+/// * `padded` - The width and height the codestream was encoded with.
+/// * `color_space` - The color space the codestream was encoded with.
+///
+/// # Returns
+///
+/// The cropped codestream, or an error message if the headers are not laid out as expected.
+fn crop_codestream(
+    codestream: &[u8],
+    size: (u32, u32),
+    padded: (u32, u32),
+    color_space: ColorSpace,
+) -> Result<Vec<u8>, String> {
+    let encoded = write_headers(padded, None, color_space);
+    let Some(toc) = codestream.strip_prefix(encoded.as_slice()) else {
+        return Err("Failed to encode JPEG XL: unexpected codestream headers".to_string());
+    };
+    let mut out = write_headers(size, Some(padded), color_space);
+    out.extend_from_slice(toc);
+    Ok(out)
+}
+
+/// Writes the image and frame headers the way zune-jpegxl does for 8-bit modular images, up
+/// to the byte-aligned table of contents.
+///
+/// # Arguments
+///
+/// * `size` - The image width and height.
+/// * `frame_size` - The frame width and height, placed at the origin, if it differs from the
+///   image size.
+/// * `color_space` - The color space of the pixels.
+///
+/// # Returns
+///
+/// The header bytes.
+fn write_headers(
+    size: (u32, u32),
+    frame_size: Option<(u32, u32)>,
+    color_space: ColorSpace,
+) -> Vec<u8> {
+    let has_alpha = color_space.has_alpha();
+    let mut out = BitWriter::default();
+
+    // Signature and size header, which is never small.
+    out.put(16, 0x0AFF);
+    out.put(1, 0);
+    for (value, is_height) in [(size.1, true), (size.0, false)] {
+        let value = u64::from(value - 1);
+        let (selector, bits) = match value {
+            v if v < 1 << 9 => (0, 9),
+            v if v < 1 << 13 => (1, 13),
+            v if v < 1 << 18 => (2, 18),
+            _ => (3, 30),
+        };
+        out.put(2, selector);
+        out.put(bits, value);
+        if is_height {
+            // No aspect ratio, so the width follows.
+            out.put(3, 0);
+        }
+    }
+
+    // Image metadata: 8-bit integer samples, sRGB or grayscale with the sRGB transfer curve.
+    out.put(1, 0); // not all default
+    out.put(1, 0); // no extra fields
+    out.put(1, 0); // integer samples
+    out.put(2, 0); // 8 bits per sample
+    out.put(1, 1); // modular 16-bit buffers suffice
+    if has_alpha {
+        out.put(2, 1); // one extra channel
+        out.put(1, 1); // a default 8-bit alpha channel
+    } else {
+        out.put(2, 0); // no extra channels
+    }
+    out.put(1, 0); // not XYB
+    if color_space.num_components() > 2 {
+        out.put(1, 1); // default sRGB color encoding
+    } else {
+        out.put(1, 0); // custom color encoding
+        out.put(1, 0); // no ICC profile
+        out.put(2, 1); // grayscale
+        out.put(2, 1); // D65 white point
+        out.put(1, 0); // no gamma
+        out.put(2, 2); // transfer function
+        out.put(4, 11); // sRGB transfer function
+        out.put(2, 1); // relative rendering intent
+    }
+    out.put(2, 0); // no extensions
+    out.put(1, 1); // default transform data
+    out.zero_pad();
+
+    // Frame header of a single, last, modular frame.
+    out.put(1, 0); // not all default
+    out.put(2, 0); // regular frame
+    out.put(1, 1); // modular
+    out.put(2, 0); // default flags
+    out.put(1, 0); // not YCbCr
+    out.put(2, 0); // no upsampling
+    if has_alpha {
+        out.put(2, 0); // no alpha upsampling
+    }
+    out.put(2, 1); // default group size
+    out.put(2, 0); // one pass
+    match frame_size {
+        Some((width, height)) => {
+            out.put(1, 1); // custom size and origin
+            // The origin is 0, 0.
+            out.put(2, 0);
+            out.put(8, 0);
+            out.put(2, 0);
+            out.put(8, 0);
+            for value in [width, height] {
+                let value = u64::from(value);
+                let (selector, bits, offset) = match value {
+                    v if v < 256 => (0, 8, 0),
+                    v if v < 2304 => (1, 11, 256),
+                    v if v < 18688 => (2, 14, 2304),
+                    _ => (3, 30, 18688),
+                };
+                out.put(2, selector);
+                out.put(bits, value - offset);
+            }
+        }
+        None => out.put(1, 0), // no custom size or origin
+    }
+    out.put(2, 0); // replace blending
+    if has_alpha {
+        out.put(2, 0); // replace blending for alpha
+    }
+    out.put(1, 1); // last frame
+    out.put(2, 0); // no name
+    out.put(1, 0); // loop filter is not all default
+    out.put(1, 0); // no Gabor-like filter
+    out.put(2, 0); // no edge-preserving filter
+    out.put(2, 0); // no loop filter extensions
+    out.put(2, 0); // no frame header extensions
+    out.put(1, 0); // no table of contents permutation
+    out.zero_pad();
+
+    out.bytes
+}
+
+/// Collects bits least significant first, the order of JPEG XL headers.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+impl BitWriter {
+    /// Appends the lowest `count` bits of `value`.
+    fn put(&mut self, count: u32, value: u64) {
+        for i in 0..count {
+            if self.bits.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            if (value >> i) & 1 == 1 {
+                *self.bytes.last_mut().expect("a byte was pushed") |= 1 << (self.bits % 8);
+            }
+            self.bits += 1;
+        }
+    }
+
+    /// Fills the current byte with zero bits.
+    fn zero_pad(&mut self) {
+        self.bits = self.bytes.len() * 8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes `pixels` and decodes the result again through the registered decoding hook.
+    fn roundtrip(pixels: &[u8], width: u32, height: u32, color: ExtendedColorType) -> Vec<u8> {
+        jxl_oxide::integration::register_image_decoding_hook();
+        let encoded = encode(pixels, width, height, color, None).unwrap();
+        assert!(is_jpeg_xl(&encoded));
+        let decoded = image::load_from_memory(&encoded).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (width, height));
+        match color {
+            ExtendedColorType::L8 => decoded.into_luma8().into_raw(),
+            ExtendedColorType::La8 => decoded.into_luma_alpha8().into_raw(),
+            ExtendedColorType::Rgb8 => decoded.into_rgb8().into_raw(),
+            _ => decoded.into_rgba8().into_raw(),
+        }
+    }
+
+    /// Returns distinct pixel values for an image of the given size.
+    fn gradient(width: u32, height: u32, channels: usize) -> Vec<u8> {
+        (0..width as usize * height as usize * channels)
+            .map(|i| (i * 37 % 251) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn encodes_losslessly() {
+        let pixels = gradient(5, 3, 3);
+        assert_eq!(roundtrip(&pixels, 5, 3, ExtendedColorType::Rgb8), pixels);
+    }
+
+    #[test]
+    fn encodes_images_below_two_pixels() {
+        for (width, height) in [(1, 1), (1, 7), (7, 1), (1, 300), (300, 1)] {
+            for (color, channels) in [
+                (ExtendedColorType::L8, 1),
+                (ExtendedColorType::La8, 2),
+                (ExtendedColorType::Rgb8, 3),
+                (ExtendedColorType::Rgba8, 4),
+            ] {
+                let pixels = gradient(width, height, channels);
+                assert_eq!(
+                    roundtrip(&pixels, width, height, color),
+                    pixels,
+                    "{width}x{height} {color:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stores_exif_in_a_container() {
+        let exif = b"MM\0\x2a\0\0\0\x08\0\0";
+        let encoded = encode(
+            &gradient(1, 2, 3),
+            1,
+            2,
+            ExtendedColorType::Rgb8,
+            Some(exif),
+        )
+        .unwrap();
+        assert!(encoded.starts_with(CONTAINER_SIGNATURE));
+        assert!(encoded.windows(exif.len()).any(|w| w == exif));
+    }
+
+    #[test]
+    fn rejects_empty_and_unsupported_images() {
+        assert!(encode(&[], 0, 4, ExtendedColorType::Rgb8, None).is_err());
+        assert!(encode(&[0; 16], 2, 2, ExtendedColorType::Rgb16, None).is_err());
+    }
+}
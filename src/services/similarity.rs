@@ -0,0 +1,169 @@
+use image::GrayImage;
+
+/// Side length of the square windows over which local statistics are computed.
+const WINDOW: u32 = 8;
+/// Distance between the top-left corners of neighbouring windows.
+const STRIDE: u32 = 4;
+/// Stabilizing constants from the original SSIM paper for 8-bit samples.
+const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+/// Computes the structural similarity (SSIM) between two grayscale images.
+///
+/// The score is the mean SSIM over overlapping 8×8 windows of the luma channel, which tracks
+/// perceived quality far better than a plain pixel difference.
+///
+/// # Arguments
+///
+/// * `reference` - The reference image, typically the geometry-adjusted source image.
+/// * `candidate` - The image to compare, typically the decoded re-encode.
+///
+/// # Returns
+///
+/// A score between `0.0` and `1.0`, where `1.0` means the images are identical. Images with
+/// different dimensions score `0.0`.
+pub fn ssim(reference: &GrayImage, candidate: &GrayImage) -> f64 {
+    if reference.dimensions() != candidate.dimensions() {
+        return 0.0;
+    }
+
+    let (width, height) = reference.dimensions();
+    if width == 0 || height == 0 {
+        return 1.0;
+    }
+
+    // Images smaller than a window are compared as a single window.
+    let window_w = WINDOW.min(width);
+    let window_h = WINDOW.min(height);
+
+    let mut total = 0.0;
+    let mut windows = 0u64;
+    let mut y = 0;
+    while y + window_h <= height {
+        let mut x = 0;
+        while x + window_w <= width {
+            total += window_ssim(reference, candidate, x, y, window_w, window_h);
+            windows += 1;
+            x += STRIDE;
+        }
+        y += STRIDE;
+    }
+
+    total / windows as f64
+}
+
+/// Computes the SSIM of a single window whose top-left corner is at `(x, y)`.
+///
+/// # Arguments
+///
+/// * `a` - The reference image.
+/// * `b` - The candidate image.
+/// * `x` - The left edge of the window.
+/// * `y` - The top edge of the window.
+/// * `w` - The window width.
+/// * `h` - The window height.
+///
+/// # Returns
+///
+/// The SSIM score of the window.
+fn window_ssim(a: &GrayImage, b: &GrayImage, x: u32, y: u32, w: u32, h: u32) -> f64 {
+    let n = (w * h) as f64;
+    let (mut sum_a, mut sum_b) = (0.0, 0.0);
+    let (mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0);
+
+    for wy in y..y + h {
+        for wx in x..x + w {
+            let pa = a.get_pixel(wx, wy).0[0] as f64;
+            let pb = b.get_pixel(wx, wy).0[0] as f64;
+            sum_a += pa;
+            sum_b += pb;
+            sum_aa += pa * pa;
+            sum_bb += pb * pb;
+            sum_ab += pa * pb;
+        }
+    }
+
+    let mean_a = sum_a / n;
+    let mean_b = sum_b / n;
+    let var_a = sum_aa / n - mean_a * mean_a;
+    let var_b = sum_bb / n - mean_b * mean_b;
+    let covariance = sum_ab / n - mean_a * mean_b;
+
+    ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+        / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a 32x24 image with a diagonal gradient and some texture.
+    fn pattern() -> GrayImage {
+        GrayImage::from_fn(32, 24, |x, y| {
+            image::Luma([((x * 7 + y * 5) % 200 + (x * y) % 37) as u8])
+        })
+    }
+
+    /// Adds `amount` of alternating noise to every pixel.
+    fn with_noise(image: &GrayImage, amount: u8) -> GrayImage {
+        GrayImage::from_fn(image.width(), image.height(), |x, y| {
+            let p = image.get_pixel(x, y)[0];
+            image::Luma([if (x + y) % 2 == 0 {
+                p.saturating_add(amount)
+            } else {
+                p.saturating_sub(amount)
+            }])
+        })
+    }
+
+    #[test]
+    fn identical_images_score_one() {
+        let image = pattern();
+        assert!((ssim(&image, &image) - 1.0).abs() < 1e-9);
+        let flat = GrayImage::from_pixel(9, 9, image::Luma([128]));
+        assert!((ssim(&flat, &flat) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn more_noise_scores_lower() {
+        let image = pattern();
+        let slight = ssim(&image, &with_noise(&image, 4));
+        let heavy = ssim(&image, &with_noise(&image, 40));
+        assert!(slight < 1.0);
+        assert!(heavy < slight);
+        assert!(heavy > 0.0);
+    }
+
+    #[test]
+    fn score_is_symmetric() {
+        let image = pattern();
+        let noisy = with_noise(&image, 20);
+        assert!((ssim(&image, &noisy) - ssim(&noisy, &image)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn inverted_image_scores_low() {
+        let image = pattern();
+        let inverted =
+            GrayImage::from_fn(32, 24, |x, y| image::Luma([255 - image.get_pixel(x, y)[0]]));
+        assert!(ssim(&image, &inverted) < 0.1);
+    }
+
+    #[test]
+    fn mismatched_dimensions_score_zero() {
+        assert_eq!(ssim(&pattern(), &GrayImage::new(24, 32)), 0.0);
+    }
+
+    #[test]
+    fn empty_images_score_one() {
+        assert_eq!(ssim(&GrayImage::new(0, 0), &GrayImage::new(0, 0)), 1.0);
+    }
+
+    #[test]
+    fn images_smaller_than_a_window_are_compared() {
+        let a = GrayImage::from_fn(3, 1, |x, _| image::Luma([x as u8 * 100]));
+        let b = GrayImage::from_fn(3, 1, |x, _| image::Luma([200 - x as u8 * 100]));
+        assert!((ssim(&a, &a) - 1.0).abs() < 1e-9);
+        assert!(ssim(&a, &b) < 0.5);
+    }
+}
@@ -1,9 +1,14 @@
 pub(crate) use crate::components::app::Message;
 use crate::components::header::get_header_with_actions;
 use crate::components::state::State;
-use crate::services::image_service::OutputFormat;
+use crate::services::image_service::{
+    ChromaSubsampling, ExistingFilePolicy, JpegOptions, OutputFormat,
+};
+use crate::views::results_view::{format_duration, format_size};
 use iced::widget::{Image, progress_bar};
-use iced::widget::{button, column, container, pick_list, row, slider, space, text, text_input};
+use iced::widget::{
+    button, checkbox, column, container, pick_list, row, slider, space, text, text_input,
+};
 use iced::{Element, Length, Theme, color};
 use iced_aw::{DropDown, drop_down, number_input};
 
@@ -35,20 +40,34 @@ pub fn view(state: &State) -> Element<'_, Message> {
     // Controls are interactive only while idle. iced disables widgets whose
     // handlers are omitted, so we attach `on_*` callbacks conditionally rather
     // than constructing separate enabled/disabled variants.
-    let enabled = !state.is_compressing;
-    let quality_enabled =
-        enabled && (state.format == OutputFormat::Jpeg || state.format == OutputFormat::WebP);
+    let enabled = !state.is_compressing
+        && state.existing_files_prompt.is_none()
+        && state.interrupted_batch.is_none();
+    let target_size_enabled = enabled && state.format.supports_quality(state.settings.lossy_png);
+    // The target size takes precedence over the similarity target, and either one
+    // replaces the fixed quality with a searched one.
+    // AVIF outputs cannot be decoded to measure their similarity, so they keep the quality.
+    let similarity_supported = state
+        .format
+        .supports_similarity(state.settings.lossy_png, &state.settings.format_mapping);
+    let similarity_enabled =
+        target_size_enabled && state.target_size_kib.is_none() && similarity_supported;
+    let quality_enabled = target_size_enabled
+        && state.target_size_kib.is_none()
+        && (state.min_similarity.is_none() || !similarity_supported);
 
     let text_input_path = text_input("", &state.input_path.join(", "))
         .on_input_maybe(enabled.then_some(|_| Message::ToggleInputDropdown));
+    // In-place compression writes next to the inputs, so there is no output folder to pick.
+    let output_enabled = enabled && !state.settings.in_place;
     let text_output_path = text_input("", &state.output_path)
-        .on_input_maybe(enabled.then_some(|_| Message::SelectOutput));
+        .on_input_maybe(output_enabled.then_some(|_| Message::SelectOutput));
 
     let dropdown_trigger =
         button(row![text(current_language.browse.as_str()), text(" \u{25BE}"),].spacing(2))
             .on_press_maybe(enabled.then_some(Message::ToggleInputDropdown));
     let browse_output_button = button(current_language.browse.as_str())
-        .on_press_maybe(enabled.then_some(Message::SelectOutput));
+        .on_press_maybe(output_enabled.then_some(Message::SelectOutput));
 
     // Sliders require an on-change handler at construction, so the disabled
     // variant maps to a no-op while the displayed value stays meaningful.
@@ -57,6 +76,21 @@ pub fn view(state: &State) -> Element<'_, Message> {
     } else {
         slider(1..=100, state.quality, |_| Message::Noop)
     };
+    // JPEG XL is only encoded losslessly, so there is no quality to pick.
+    let quality_row = if state.format == OutputFormat::JpegXl {
+        row![
+            text(current_language.quality.as_str()).width(Length::Fixed(LABEL_WIDTH)),
+            text(current_language.jpeg_xl_lossless.as_str()).width(Length::Fill),
+        ]
+    } else {
+        row![
+            text(current_language.quality.as_str()).width(Length::Fixed(LABEL_WIDTH)),
+            quality_slider.width(Length::Fill),
+            text(format!("{}%", state.quality)).width(Length::Shrink),
+        ]
+    }
+    .spacing(10)
+    .align_y(iced::Alignment::Center);
     let scale_slider = if enabled {
         slider(1..=100, state.scale, Message::CompressionScaleChanged)
     } else {
@@ -131,6 +165,78 @@ pub fn view(state: &State) -> Element<'_, Message> {
     .on_dismiss(Message::DismissInputDropdown)
     .alignment(drop_down::Alignment::Bottom);
 
+    // Advanced JPEG options apply to JPEG outputs, including inputs mapped to JPEG when
+    // keeping the original format.
+    let jpeg_options_enabled =
+        enabled && matches!(state.format, OutputFormat::Jpeg | OutputFormat::Original);
+    let options = state.jpeg_options;
+    let jpeg_options_overlay = container(
+        column![
+            checkbox(options.progressive)
+                .label(current_language.progressive_jpeg.as_str())
+                .on_toggle(move |progressive| {
+                    Message::JpegOptionsChanged(JpegOptions {
+                        progressive,
+                        ..options
+                    })
+                }),
+            checkbox(options.optimize_huffman)
+                .label(current_language.optimize_huffman.as_str())
+                .on_toggle(move |optimize_huffman| {
+                    Message::JpegOptionsChanged(JpegOptions {
+                        optimize_huffman,
+                        ..options
+                    })
+                }),
+            checkbox(options.trellis)
+                .label(current_language.trellis_quantization.as_str())
+                .on_toggle(move |trellis| {
+                    Message::JpegOptionsChanged(JpegOptions { trellis, ..options })
+                }),
+            checkbox(options.lossless)
+                .label(current_language.lossless_jpeg.as_str())
+                .on_toggle(move |lossless| {
+                    Message::JpegOptionsChanged(JpegOptions {
+                        lossless,
+                        ..options
+                    })
+                }),
+            row![
+                text(current_language.chroma_subsampling.as_str()),
+                pick_list(
+                    &ChromaSubsampling::ALL[..],
+                    Some(options.chroma_subsampling),
+                    move |chroma_subsampling| {
+                        Message::JpegOptionsChanged(JpegOptions {
+                            chroma_subsampling,
+                            ..options
+                        })
+                    }
+                ),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center),
+        ]
+        .spacing(10)
+        .padding(10),
+    )
+    .style(container::bordered_box);
+
+    let jpeg_options_dropdown = DropDown::new(
+        button(
+            row![
+                text(current_language.jpeg_options.as_str()),
+                text(" \u{25BE}"),
+            ]
+            .spacing(2),
+        )
+        .on_press_maybe(jpeg_options_enabled.then_some(Message::ToggleJpegOptionsDropdown)),
+        jpeg_options_overlay,
+        state.show_jpeg_options_dropdown && jpeg_options_enabled,
+    )
+    .on_dismiss(Message::DismissJpegOptionsDropdown)
+    .alignment(drop_down::Alignment::BottomStart);
+
     let width = state.width.unwrap_or(0) as i32;
     let height = state.height.unwrap_or(0) as i32;
 
@@ -152,6 +258,24 @@ pub fn view(state: &State) -> Element<'_, Message> {
     .width(Length::Fill)
     .step(1);
 
+    let target_size = state.target_size_kib.unwrap_or(0) as i32;
+    let target_size_input = if target_size_enabled {
+        number_input(&target_size, 0..=i32::MAX, Message::TargetSizeChanged)
+    } else {
+        number_input(&target_size, 0..=i32::MAX, |_| Message::Noop)
+    }
+    .width(Length::Fill)
+    .step(10);
+
+    let min_similarity = state.min_similarity.unwrap_or(0.0);
+    let min_similarity_input = if similarity_enabled {
+        number_input(&min_similarity, 0.0..=100.0, Message::MinSimilarityChanged)
+    } else {
+        number_input(&min_similarity, 0.0..=100.0, |_| Message::Noop)
+    }
+    .width(Length::Fill)
+    .step(0.5);
+
     let content = iced::widget::column![
         row![
             text(current_language.input.as_str()).width(Length::Fixed(LABEL_WIDTH)),
@@ -170,16 +294,28 @@ pub fn view(state: &State) -> Element<'_, Message> {
         row![
             text(current_language.format.as_str()).width(Length::Fixed(LABEL_WIDTH)),
             format_pick_list.width(Length::Fill),
+            container(jpeg_options_dropdown).width(Length::Shrink),
         ]
         .spacing(10)
         .align_y(iced::Alignment::Center),
+        quality_row,
         row![
-            text(current_language.quality.as_str()).width(Length::Fixed(LABEL_WIDTH)),
-            quality_slider.width(Length::Fill),
-            text(format!("{}%", state.quality)).width(Length::Shrink),
+            row![
+                text(current_language.target_size.as_str()).width(Length::Fixed(LABEL_WIDTH)),
+                target_size_input,
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center)
+            .width(Length::Fill),
+            row![
+                text(current_language.min_similarity.as_str()).width(Length::Fixed(LABEL_WIDTH)),
+                min_similarity_input,
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center)
+            .width(Length::Fill),
         ]
-        .spacing(10)
-        .align_y(iced::Alignment::Center),
+        .spacing(15),
         row![
             text(current_language.scale.as_str()).width(Length::Fixed(LABEL_WIDTH)),
             scale_slider.width(Length::Fill),
@@ -212,20 +348,132 @@ pub fn view(state: &State) -> Element<'_, Message> {
                 } else {
                     0.0
                 };
+                let paused = state.compression_queue.is_paused();
+                let pause_button = if paused {
+                    button(current_language.resume.as_str()).on_press_maybe(
+                        (!state.is_cancelling).then_some(Message::ResumeCompression),
+                    )
+                } else {
+                    button(current_language.pause.as_str())
+                        .on_press_maybe((!state.is_cancelling).then_some(Message::PauseCompression))
+                };
+                let progress_text = if paused {
+                    &current_language.paused_progress
+                } else {
+                    &current_language.compressing_progress
+                };
+                // Throughput counts the source bytes of the files finished in this session; the
+                // estimate extrapolates their average time to the files that are left. Time spent
+                // paused counts towards neither.
+                let statistics = state
+                    .progress_elapsed()
+                    .map(|(elapsed, completed_before)| {
+                        let processed: u64 = state
+                            .compression_results
+                            .iter()
+                            .map(|r| r.original_size)
+                            .sum();
+                        let speed = processed as f64 / elapsed.as_secs_f64().max(0.001);
+                        let done = state.progress_completed.saturating_sub(completed_before);
+                        let left = state
+                            .progress_total
+                            .saturating_sub(state.progress_completed);
+                        let remaining = if done > 0 {
+                            format_duration(elapsed.mul_f64(left as f64 / done as f64))
+                        } else {
+                            "-".to_string()
+                        };
+                        current_language
+                            .batch_statistics
+                            .replace("{elapsed}", &format_duration(elapsed))
+                            .replace("{speed}", &format!("{}/s", format_size(speed as u64)))
+                            .replace("{remaining}", &remaining)
+                    })
+                    .unwrap_or_default();
+                let cancel_button = if state.is_cancelling {
+                    button(current_language.cancelling.as_str())
+                } else {
+                    button(current_language.cancel.as_str()).on_press(Message::CancelCompression)
+                }
+                .style(button::secondary);
                 row![
-                    progress_bar(0.0..=1.0, progress),
+                    progress_bar(0.0..=1.0, progress).style(if paused {
+                        progress_bar::warning
+                    } else {
+                        progress_bar::primary
+                    }),
                     text(
-                        current_language
-                            .compressing_progress
+                        progress_text
                             .replace("{completed}", &state.progress_completed.to_string())
                             .replace("{total}", &state.progress_total.to_string())
                     ),
+                    text(statistics).size(12),
+                    pause_button,
+                    cancel_button,
+                ]
+                .spacing(8)
+                .align_y(iced::Alignment::Center)
+                .into()
+            } else if let Some(count) = state.existing_files_prompt {
+                row![
+                    text(
+                        current_language
+                            .existing_files_prompt
+                            .replace("{count}", &count.to_string())
+                    )
+                    .width(Length::Fill),
+                    button(current_language.overwrite.as_str()).on_press(
+                        Message::ExistingFilesResolved(Some(ExistingFilePolicy::Overwrite))
+                    ),
+                    button(current_language.skip.as_str()).on_press(
+                        Message::ExistingFilesResolved(Some(ExistingFilePolicy::Skip))
+                    ),
+                    button(current_language.rename.as_str()).on_press(
+                        Message::ExistingFilesResolved(Some(ExistingFilePolicy::Rename))
+                    ),
+                    button(current_language.cancel.as_str())
+                        .style(button::secondary)
+                        .on_press(Message::ExistingFilesResolved(None)),
+                ]
+                .spacing(8)
+                .align_y(iced::Alignment::Center)
+                .into()
+            } else if let Some(journal) = &state.interrupted_batch {
+                let total = journal.files.len();
+                let remaining = total - journal.finished.len();
+                row![
+                    text(
+                        current_language
+                            .interrupted_batch_prompt
+                            .replace("{remaining}", &remaining.to_string())
+                            .replace("{total}", &total.to_string())
+                    )
+                    .width(Length::Fill),
+                    button(current_language.resume.as_str())
+                        .on_press(Message::InterruptedBatchResolved(true)),
+                    button(current_language.discard.as_str())
+                        .style(button::secondary)
+                        .on_press(Message::InterruptedBatchResolved(false)),
                 ]
                 .spacing(8)
                 .align_y(iced::Alignment::Center)
                 .into()
             } else {
-                row![space::horizontal(), compress_button,].into()
+                let preview = match state.cancelled_summary {
+                    Some((completed, total)) => current_language
+                        .compression_cancelled
+                        .replace("{completed}", &completed.to_string())
+                        .replace("{total}", &total.to_string()),
+                    None => state
+                        .file_name_preview
+                        .as_deref()
+                        .map(|name| format!("{}: {name}", current_language.output_name_preview))
+                        .unwrap_or_default(),
+                };
+                row![text(preview).width(Length::Fill), compress_button,]
+                    .spacing(10)
+                    .align_y(iced::Alignment::Center)
+                    .into()
             };
             row![status_widget]
         },
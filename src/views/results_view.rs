@@ -1,11 +1,14 @@
 use crate::components::app::Message;
 use crate::components::header::get_header;
 use crate::components::state::State;
-use iced::widget::{button, column, container, row, scrollable, space, text};
+use crate::services::image_service::OutputAction;
+use iced::widget::{button, column, container, row, scrollable, space, text, tooltip};
 use iced::{Element, Length, color};
+use std::path::Path;
+use std::time::Duration;
 
 /// Formats a byte count as a human-readable string (B, KiB, MiB, GiB).
-fn format_size(bytes: u64) -> String {
+pub(crate) fn format_size(bytes: u64) -> String {
     const KI_B: u64 = 1024;
     const MI_B: u64 = 1024 * KI_B;
     const GI_B: u64 = 1024 * MI_B;
@@ -21,8 +24,20 @@ fn format_size(bytes: u64) -> String {
     }
 }
 
+/// Formats a duration as seconds below a minute, and as `m:ss` or `h:mm:ss` above.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{:.1} s", duration.as_secs_f64())
+    } else if secs < 3600 {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    } else {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    }
+}
+
 /// Formats a percentage with a `+` prefix when the file grew and `-` when it shrank.
-fn format_pct(pct: f64) -> String {
+pub(crate) fn format_pct(pct: f64) -> String {
     if pct == 0.0 {
         "0.0%".to_string()
     } else if pct > 0.0 {
@@ -57,6 +72,9 @@ pub fn view(state: &State) -> Element<'_, Message> {
         text(lang.original.as_str()).width(Length::FillPortion(2)),
         text(lang.compressed_size.as_str()).width(Length::FillPortion(2)),
         text(lang.saved.as_str()).width(Length::FillPortion(2)),
+        text(lang.used_quality.as_str()).width(Length::FillPortion(1)),
+        text(lang.similarity.as_str()).width(Length::FillPortion(2)),
+        text(lang.duration.as_str()).width(Length::FillPortion(2)),
     ]
     .spacing(8)
     .padding([4, 8]);
@@ -66,10 +84,50 @@ pub fn view(state: &State) -> Element<'_, Message> {
         .iter()
         .map(|r| {
             row![
-                text(r.file_name.as_str()).width(Length::FillPortion(4)),
+                text(match r.output_path.as_deref().filter(|_| r.renamed) {
+                    Some(path) => format!(
+                        "{} \u{2192} {}",
+                        r.file_name,
+                        Path::new(path)
+                            .file_name()
+                            .map_or_else(|| path.into(), |n| n.to_string_lossy())
+                    ),
+                    None => r.file_name.clone(),
+                })
+                .width(Length::FillPortion(4)),
                 text(format_size(r.original_size)).width(Length::FillPortion(2)),
                 text(format_size(r.compressed_size)).width(Length::FillPortion(2)),
-                text(format_pct(r.percent_saved())).width(Length::FillPortion(2)),
+                text(match r.action {
+                    OutputAction::Written => format_pct(r.percent_saved()),
+                    OutputAction::KeptOriginal => lang.original_kept.clone(),
+                    OutputAction::Skipped => lang.skipped.clone(),
+                    OutputAction::SkippedExisting => lang.already_exists.clone(),
+                })
+                .width(Length::FillPortion(2)),
+                text(
+                    r.chosen_quality
+                        .map_or_else(|| "-".to_string(), |q| q.to_string())
+                )
+                .width(Length::FillPortion(1)),
+                text(
+                    r.similarity
+                        .map_or_else(|| "-".to_string(), |s| format!("{:.2}%", s * 100.0))
+                )
+                .width(Length::FillPortion(2)),
+                container(tooltip(
+                    text(format_duration(r.timings.total())),
+                    container(text(
+                        lang.phase_timings
+                            .replace("{decode}", &format_duration(r.timings.decode))
+                            .replace("{geometry}", &format_duration(r.timings.geometry))
+                            .replace("{encode}", &format_duration(r.timings.encode))
+                            .replace("{write}", &format_duration(r.timings.write))
+                    ))
+                    .padding(6)
+                    .style(container::rounded_box),
+                    tooltip::Position::Top,
+                ))
+                .width(Length::FillPortion(2)),
             ]
             .spacing(8)
             .padding([2, 8])
@@ -100,6 +158,7 @@ pub fn view(state: &State) -> Element<'_, Message> {
         text(format_size(total_original)).width(Length::FillPortion(2)),
         text(format_size(total_compressed)).width(Length::FillPortion(2)),
         text(format_pct(total_pct)).width(Length::FillPortion(2)),
+        space::horizontal().width(Length::FillPortion(5)),
     ]
     .spacing(8)
     .padding([4, 8]);
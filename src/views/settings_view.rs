@@ -1,8 +1,15 @@
 use crate::components::app::Message;
 use crate::components::header::get_header;
+use crate::components::settings::{BackupLocation, DeleteMode};
 use crate::components::state::State;
-use iced::widget::{button, checkbox, container, pick_list, row, space, text};
+use crate::models::labeled_option::LabeledOption;
+use crate::services::file_name_template::Token;
+use crate::services::image_service::{ExistingFilePolicy, LargerOutputPolicy, OutputFormat};
+use iced::widget::{
+    button, checkbox, container, pick_list, row, scrollable, space, text, text_input,
+};
 use iced::{Element, Length, Theme, color};
+use iced_aw::number_input;
 
 /// Builds the settings view of the application, allowing users to adjust preferences such as auto-update, file deletion after compression, and theme selection.
 ///
@@ -21,7 +28,99 @@ pub fn view(state: &State) -> Element<'_, Message> {
         color!(48, 48, 48, 0.8),
     );
 
-    let content = iced::widget::column![
+    let larger_output_options: Vec<LabeledOption<LargerOutputPolicy>> = LargerOutputPolicy::ALL
+        .into_iter()
+        .map(|policy| {
+            let label = match policy {
+                LargerOutputPolicy::WriteAnyway => &current_language.write_anyway,
+                LargerOutputPolicy::KeepOriginal => &current_language.keep_original,
+                LargerOutputPolicy::Skip => &current_language.skip,
+            };
+            LabeledOption::new(policy, label)
+        })
+        .collect();
+    let selected_larger_output = larger_output_options
+        .iter()
+        .find(|o| o.value == state.settings.larger_output_policy)
+        .cloned();
+
+    let delete_mode_options: Vec<LabeledOption<DeleteMode>> = DeleteMode::ALL
+        .into_iter()
+        .map(|mode| {
+            let label = match mode {
+                DeleteMode::Trash => &current_language.move_to_trash,
+                DeleteMode::Permanent => &current_language.delete_permanently,
+            };
+            LabeledOption::new(mode, label)
+        })
+        .collect();
+    let selected_delete_mode = delete_mode_options
+        .iter()
+        .find(|o| o.value == state.settings.delete_mode)
+        .cloned();
+
+    let backup_location_options: Vec<LabeledOption<BackupLocation>> = BackupLocation::ALL
+        .into_iter()
+        .map(|location| {
+            let label = match location {
+                BackupLocation::Timestamped => &current_language.backup_timestamped,
+                BackupLocation::Sibling => &current_language.backup_sibling,
+            };
+            LabeledOption::new(location, label)
+        })
+        .collect();
+    let selected_backup_location = backup_location_options
+        .iter()
+        .find(|o| o.value == state.settings.backup_location)
+        .cloned();
+
+    let existing_file_options: Vec<LabeledOption<ExistingFilePolicy>> = ExistingFilePolicy::ALL
+        .into_iter()
+        .map(|policy| {
+            let label = match policy {
+                ExistingFilePolicy::Overwrite => &current_language.overwrite,
+                ExistingFilePolicy::Skip => &current_language.skip,
+                ExistingFilePolicy::Rename => &current_language.rename,
+                ExistingFilePolicy::Ask => &current_language.ask,
+            };
+            LabeledOption::new(policy, label)
+        })
+        .collect();
+    let selected_existing_file = existing_file_options
+        .iter()
+        .find(|o| o.value == state.settings.existing_file_policy)
+        .cloned();
+
+    // One row per pair of source formats, each with the format it is written as when the
+    // output format is set to keep the original.
+    let format_mapping_rows = OutputFormat::SOURCES.chunks(2).map(|sources| {
+        row(sources.iter().map(|&source| {
+            let target = state
+                .settings
+                .format_mapping
+                .get(&source)
+                .copied()
+                .unwrap_or(source);
+            row![
+                text(source.to_string()).width(Length::FillPortion(1)),
+                pick_list(OutputFormat::ENCODABLE, Some(target), move |t| {
+                    Message::FormatMappingChanged(source, t)
+                })
+                .width(Length::FillPortion(2))
+            ]
+            .spacing(10)
+            .width(Length::FillPortion(1))
+            .into()
+        }))
+        .spacing(20)
+        .into()
+    });
+
+    let max_concurrent_jobs = state.settings.max_concurrent_jobs as i32;
+    let pixel_budget = state.settings.pixel_budget_megapixels.unwrap_or(0) as i32;
+    let max_frame_rate = state.settings.max_frame_rate.unwrap_or(0) as i32;
+
+    let options = iced::widget::column![
         row![
             checkbox(state.settings.auto_update)
                 .label(current_language.automatically_check_for_updates.as_str())
@@ -39,7 +138,15 @@ pub fn view(state: &State) -> Element<'_, Message> {
                         .delete_original_files_after_compression
                         .as_str()
                 )
-                .on_toggle(Message::DeleteFilesAfterCompressionToggled)
+                .on_toggle_maybe(
+                    (!state.settings.in_place)
+                        .then_some(Message::DeleteFilesAfterCompressionToggled)
+                )
+        ],
+        row![
+            checkbox(state.settings.in_place)
+                .label(current_language.in_place.as_str())
+                .on_toggle(Message::InPlaceToggled)
         ],
         row![
             checkbox(state.settings.preserve_exif)
@@ -51,6 +158,119 @@ pub fn view(state: &State) -> Element<'_, Message> {
                 .label(current_language.recursive_folder_scan.as_str())
                 .on_toggle(Message::RecursiveFolderScanToggled)
         ],
+        row![
+            checkbox(state.settings.preserve_folder_structure)
+                .label(current_language.preserve_folder_structure.as_str())
+                .on_toggle(Message::PreserveFolderStructureToggled)
+        ],
+        row![
+            checkbox(state.settings.downscale_to_target_size)
+                .label(current_language.downscale_to_target_size.as_str())
+                .on_toggle(Message::DownscaleToTargetSizeToggled)
+        ],
+        row![
+            checkbox(state.settings.png_zopfli)
+                .label(current_language.png_zopfli.as_str())
+                .on_toggle(Message::PngZopfliToggled)
+        ],
+        row![
+            checkbox(state.settings.lossy_png)
+                .label(current_language.lossy_png.as_str())
+                .on_toggle(Message::LossyPngToggled)
+        ],
+        row![
+            checkbox(state.settings.optimize_gif_palette)
+                .label(current_language.optimize_gif_palette.as_str())
+                .on_toggle(Message::OptimizeGifPaletteToggled)
+        ],
+        row![
+            text(current_language.larger_output.as_str()).width(Length::FillPortion(1)),
+            pick_list(
+                larger_output_options,
+                selected_larger_output,
+                |o: LabeledOption<LargerOutputPolicy>| Message::LargerOutputPolicyChanged(o.value)
+            )
+            .width(Length::FillPortion(3))
+        ]
+        .spacing(20),
+        row![
+            text(current_language.delete_mode.as_str()).width(Length::FillPortion(1)),
+            pick_list(
+                delete_mode_options,
+                selected_delete_mode,
+                |o: LabeledOption<DeleteMode>| Message::DeleteModeChanged(o.value)
+            )
+            .width(Length::FillPortion(3))
+        ]
+        .spacing(20),
+        row![
+            text(current_language.backup_location.as_str()).width(Length::FillPortion(1)),
+            pick_list(
+                backup_location_options,
+                selected_backup_location,
+                |o: LabeledOption<BackupLocation>| Message::BackupLocationChanged(o.value)
+            )
+            .width(Length::FillPortion(3))
+        ]
+        .spacing(20),
+        row![
+            text(current_language.existing_files.as_str()).width(Length::FillPortion(1)),
+            pick_list(
+                existing_file_options,
+                selected_existing_file,
+                |o: LabeledOption<ExistingFilePolicy>| Message::ExistingFilePolicyChanged(o.value)
+            )
+            .width(Length::FillPortion(3))
+        ]
+        .spacing(20),
+        row![
+            text(current_language.png_optimization_level.as_str()).width(Length::FillPortion(1)),
+            pick_list(
+                (0..=6).collect::<Vec<u8>>(),
+                Some(state.settings.png_optimization_level),
+                Message::PngOptimizationLevelChanged
+            )
+            .width(Length::FillPortion(3))
+        ]
+        .spacing(20),
+        row![
+            text(current_language.avif_speed.as_str()).width(Length::FillPortion(1)),
+            pick_list(
+                (1..=10).collect::<Vec<u8>>(),
+                Some(state.settings.avif_speed),
+                Message::AvifSpeedChanged
+            )
+            .width(Length::FillPortion(3))
+        ]
+        .spacing(20),
+        row![
+            text(current_language.max_frame_rate.as_str()).width(Length::FillPortion(1)),
+            number_input(&max_frame_rate, 0..=1000, Message::MaxFrameRateChanged)
+                .width(Length::FillPortion(3))
+                .step(1)
+        ]
+        .spacing(20)
+        .align_y(iced::Alignment::Center),
+        row![
+            text(current_language.max_concurrent_jobs.as_str()).width(Length::FillPortion(1)),
+            number_input(
+                &max_concurrent_jobs,
+                1..=256,
+                Message::MaxConcurrentJobsChanged
+            )
+            .width(Length::FillPortion(3))
+            .step(1)
+        ]
+        .spacing(20)
+        .align_y(iced::Alignment::Center),
+        row![
+            text(current_language.pixel_budget.as_str()).width(Length::FillPortion(1)),
+            number_input(&pixel_budget, 0..=i32::MAX, Message::PixelBudgetChanged)
+                .width(Length::FillPortion(3))
+                .step(10)
+        ]
+        .spacing(20)
+        .align_y(iced::Alignment::Center),
         row![
             text(current_language.theme.as_str()).width(Length::FillPortion(1)),
             pick_list(
@@ -77,7 +297,28 @@ pub fn view(state: &State) -> Element<'_, Message> {
             .width(Length::FillPortion(3))
         ]
         .spacing(20),
-        row![space::vertical(),],
+        row![
+            text(current_language.file_name_template.as_str()).width(Length::FillPortion(1)),
+            text_input("", &state.settings.file_name_template)
+                .on_input(Message::FileNameTemplateChanged)
+                .width(Length::FillPortion(3))
+        ]
+        .spacing(20)
+        .align_y(iced::Alignment::Center),
+        text(format!(
+            "{}: {}",
+            current_language.available_tokens,
+            Token::ALL.map(|t| format!("{{{}}}", t.name())).join(" ")
+        ))
+        .size(12),
+        text(current_language.format_mapping.as_str()),
+        iced::widget::column(format_mapping_rows).spacing(10),
+    ]
+    .spacing(15)
+    .padding(15);
+
+    let content = iced::widget::column![
+        scrollable(options).height(Length::Fill),
         row![
             button(current_language.check_for_updates.as_str())
                 .width(Length::Shrink)
@@ -88,9 +329,8 @@ pub fn view(state: &State) -> Element<'_, Message> {
                 .width(Length::Shrink)
                 .on_press(Message::ResetSettings),
         ]
-    ]
-    .spacing(15)
-    .padding(15);
+        .padding(15)
+    ];
 
     let together = iced::widget::column![header, content];
 